@@ -0,0 +1,371 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env,
+    error::Error,
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+#[cfg(not(windows))]
+mod custom;
+#[cfg(not(windows))]
+mod desktop;
+#[cfg(not(windows))]
+mod feh;
+#[cfg(not(windows))]
+mod setters;
+#[cfg(not(windows))]
+mod sway;
+#[cfg(windows)]
+mod windows;
+#[cfg(all(not(windows), feature = "x11"))]
+mod x11;
+
+#[cfg(windows)]
+pub use self::windows::Windows;
+#[cfg(all(not(windows), feature = "x11"))]
+pub use self::x11::X11;
+#[cfg(not(windows))]
+pub use self::{
+    custom::Custom,
+    desktop::{Gnome, Kde, Lxde, Mate, Pantheon, Xfce},
+    feh::Feh,
+    setters::{Hyprpaper, Nitrogen, Swww, Wbg, Xwallpaper},
+    sway::Sway,
+};
+
+///A method of changing the wallpaper, such as a desktop environment's own settings command
+pub trait Backend: Send + Sync {
+    ///Name of the backend, used in messages
+    fn name(&self) -> &str;
+
+    ///Returns true if this backend handles the given desktop. `desktop` is either the value of
+    ///`XDG_CURRENT_DESKTOP` or the name the user passed through the backend option
+    fn detect(&self, desktop: &str) -> bool;
+
+    ///Sets the image at `filepath` as the wallpaper. Backends that can't do what's asked for in
+    ///`options` (like setting only one monitor) ignore it
+    fn set(&self, filepath: &str, options: &SetOptions) -> Result<(), Box<dyn Error>>;
+
+    ///Returns the wallpaper currently set through this backend, if it can be queried
+    fn current(&self) -> Option<String> {
+        None
+    }
+}
+
+///How the wallpaper should be set, on top of the image to use
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SetOptions {
+    ///Names of the monitors to set the wallpaper on, separated by commas, or all of them if not
+    ///given
+    pub monitor: Option<String>,
+    ///Name of the transition to the new wallpaper, for backends that have them
+    pub transition: Option<String>,
+    ///How long the transition takes, in seconds
+    pub transition_duration: Option<f64>,
+    ///How the image is fitted to the screen, or the backend's own default if not given
+    pub mode: Option<Mode>,
+    ///Modes used instead of `mode` for particular images or directories, see `for_image`
+    pub modes: BTreeMap<String, Mode>,
+    ///Shows the matching image from the night directory with the dark style, for backends that
+    ///have one
+    pub dark_night: bool,
+}
+
+impl SetOptions {
+    ///Returns the names of the monitors in `monitor`, which is empty if they are all to be set
+    pub fn monitors(&self) -> Vec<&str> {
+        self.monitor
+            .iter()
+            .flat_map(|monitor| monitor.split(','))
+            .map(str::trim)
+            .filter(|monitor| !monitor.is_empty())
+            .collect()
+    }
+
+    ///Returns the options to use for the image at `filepath`. If a key in `modes` matches the
+    ///end of the image's path (like `3.png` or `night/3.png`), or else the end of the directory
+    ///it's in (like `night`), that mode is used. When several keys match, the one with the most
+    ///path components wins
+    pub fn for_image(&self, filepath: &str) -> SetOptions {
+        let matching = |path: Option<&Path>| {
+            self.modes
+                .iter()
+                .filter(|(key, _)| path.is_some_and(|path| path.ends_with(key)))
+                .max_by_key(|(key, _)| Path::new(key).components().count())
+                .map(|(_, mode)| *mode)
+        };
+        let path = Path::new(filepath);
+        SetOptions {
+            mode: matching(Some(path))
+                .or_else(|| matching(path.parent()))
+                .or(self.mode),
+            ..self.clone()
+        }
+    }
+}
+
+///How an image that isn't the same size as the screen is fitted to it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    ///Scaled to cover the whole screen, cutting off what doesn't fit
+    #[default]
+    Fill,
+    ///Scaled to fit inside the screen, with borders where it doesn't cover it
+    Fit,
+    ///Shown at its own size in the middle of the screen
+    Center,
+    ///Repeated from the top left corner at its own size
+    Tile,
+    ///Scaled to the size of the screen, without keeping its shape
+    Stretch,
+    ///Scaled to cover all the monitors as one, rather than each one
+    Span,
+}
+
+//backends registered by library users are kept here. They are checked before the built in ones,
+//so a desktop can be taken over without forking
+static REGISTERED: RwLock<Vec<Arc<dyn Backend>>> = RwLock::new(Vec::new());
+
+///Registers a backend to be considered when picking how to set the wallpaper. Backends
+///registered later take priority over ones registered earlier, and all of them take priority
+///over the built in backends
+pub fn register<B: Backend + 'static>(backend: B) {
+    REGISTERED
+        .write()
+        .expect("Backend registry poisoned")
+        .push(Arc::new(backend));
+}
+
+///Returns all backends in the order they are checked
+pub fn registry() -> Vec<Arc<dyn Backend>> {
+    let mut backends: Vec<Arc<dyn Backend>> = REGISTERED
+        .read()
+        .expect("Backend registry poisoned")
+        .iter()
+        .rev()
+        .cloned()
+        .collect();
+    backends.extend(builtins());
+    backends
+}
+
+#[cfg(not(windows))]
+fn builtins() -> Vec<Arc<dyn Backend>> {
+    vec![
+        Arc::new(Gnome),
+        Arc::new(Lxde),
+        Arc::new(Pantheon),
+        Arc::new(Mate),
+        Arc::new(Kde),
+        Arc::new(Xfce),
+        Arc::new(Sway),
+        Arc::new(Hyprpaper),
+        Arc::new(Swww),
+        Arc::new(Wbg),
+        Arc::new(Xwallpaper),
+        Arc::new(Nitrogen),
+        #[cfg(feature = "x11")]
+        Arc::new(X11),
+        Arc::new(Feh),
+    ]
+}
+
+#[cfg(windows)]
+fn builtins() -> Vec<Arc<dyn Backend>> {
+    vec![Arc::new(Windows)]
+}
+
+///Picks the backend to use. If the user specified a backend, the first backend that recognizes
+///the name is used, and if none do, the string is treated as a custom command. Otherwise the
+///current desktop is detected through `XDG_CURRENT_DESKTOP`. Without a desktop environment, the
+///compositor's own backend is used if there is one, or else the first wallpaper setter that's
+///installed for Wayland (`WAYLAND_DISPLAY` is set) or X11, falling back on the built in X11
+///backend when the x11 feature is enabled, or else feh
+#[cfg(not(windows))]
+pub fn resolve(backend: Option<&str>) -> Arc<dyn Backend> {
+    let registry = registry();
+
+    if let Some(back) = backend {
+        return match registry.into_iter().find(|b| b.detect(back)) {
+            Some(found) => found,
+            None => Arc::new(Custom::new(back)),
+        };
+    }
+
+    let curr_de = env::var("XDG_CURRENT_DESKTOP").unwrap_or_else(|_| String::from("Other"));
+    if let Some(found) = registry.into_iter().find(|b| b.detect(&curr_de)) {
+        return found;
+    }
+
+    let running = |var: &str| env::var_os(var).is_some();
+    fallback(running, installed)
+}
+
+//picks the backend for the display server when there's no desktop environment, going by the
+//environment variables set (`running`) and the programs in PATH (`installed`)
+#[cfg(not(windows))]
+fn fallback(running: impl Fn(&str) -> bool, installed: impl Fn(&str) -> bool) -> Arc<dyn Backend> {
+    let fallbacks: Vec<(bool, Arc<dyn Backend>)> = if running("WAYLAND_DISPLAY") {
+        vec![
+            (running("SWAYSOCK"), Arc::new(Sway)),
+            (running("HYPRLAND_INSTANCE_SIGNATURE"), Arc::new(Hyprpaper)),
+            (installed("swww"), Arc::new(Swww)),
+            (installed("wbg"), Arc::new(Wbg)),
+        ]
+    } else {
+        vec![
+            (installed("feh"), Arc::new(Feh)),
+            (installed("xwallpaper"), Arc::new(Xwallpaper)),
+            (installed("nitrogen"), Arc::new(Nitrogen)),
+            #[cfg(feature = "x11")]
+            (true, Arc::new(X11)),
+        ]
+    };
+    match fallbacks.into_iter().find(|(available, _)| *available) {
+        Some((_, found)) => found,
+        None => Arc::new(Feh),
+    }
+}
+
+//returns true if the program can be found in PATH
+#[cfg(not(windows))]
+fn installed(program: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|path| env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+#[cfg(windows)]
+pub fn resolve(backend: Option<&str>) -> Arc<dyn Backend> {
+    let registry = registry();
+
+    if let Some(back) = backend {
+        if let Some(found) = registry.iter().find(|b| b.detect(back)) {
+            return Arc::clone(found);
+        }
+        eprintln!("NOTE: You are unable to select a backend on windows");
+    }
+
+    let curr_de = env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    match registry.into_iter().find(|b| b.detect(&curr_de)) {
+        Some(found) => found,
+        None => Arc::new(Windows),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_image_uses_the_most_specific_match() {
+        let options = SetOptions {
+            mode: Some(Mode::Fill),
+            modes: BTreeMap::from([
+                ("3.png".to_string(), Mode::Tile),
+                ("night".to_string(), Mode::Fit),
+                ("night/3.png".to_string(), Mode::Center),
+                ("wallpapers/night".to_string(), Mode::Stretch),
+            ]),
+            ..SetOptions::default()
+        };
+        let mode = |path| options.for_image(path).mode.unwrap();
+        assert_eq!(mode("/home/me/wallpapers/night/3.png"), Mode::Center);
+        assert_eq!(mode("/home/me/wallpapers/day/3.png"), Mode::Tile);
+        assert_eq!(mode("/home/me/wallpapers/night/1.png"), Mode::Stretch);
+        assert_eq!(mode("/home/me/other/night/1.png"), Mode::Fit);
+        assert_eq!(mode("/home/me/wallpapers/day/1.png"), Mode::Fill);
+    }
+
+    #[test]
+    fn monitors_are_separated_by_commas() {
+        let monitors = |monitor: Option<&str>| {
+            let options = SetOptions {
+                monitor: monitor.map(String::from),
+                ..SetOptions::default()
+            };
+            options.monitors().join("|")
+        };
+        assert_eq!(monitors(None), "");
+        assert_eq!(monitors(Some("HDMI-A-1")), "HDMI-A-1");
+        assert_eq!(monitors(Some("HDMI-A-1, DP-2,")), "HDMI-A-1|DP-2");
+    }
+
+    struct Takeover;
+
+    impl Backend for Takeover {
+        fn name(&self) -> &str {
+            "takeover"
+        }
+
+        fn detect(&self, desktop: &str) -> bool {
+            unicase::eq(desktop, "gnome") || unicase::eq(desktop, "takeover")
+        }
+
+        fn set(&self, _: &str, _: &SetOptions) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn registered_backends_come_before_built_in_ones() {
+        assert_eq!(resolve(Some("feh")).name(), "Feh");
+        register(Takeover);
+        assert_eq!(resolve(Some("GNOME")).name(), "takeover");
+        assert_eq!(resolve(Some("takeover")).name(), "takeover");
+        assert_eq!(registry()[0].name(), "takeover");
+        //built in backends that weren't taken over are still found
+        assert_eq!(resolve(Some("feh")).name(), "Feh");
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn fallback_goes_by_the_display_server() {
+        let pick = |vars: &[&str], programs: &[&str]| {
+            let running = |var: &str| vars.contains(&var);
+            let installed = |program: &str| programs.contains(&program);
+            fallback(running, installed).name().to_string()
+        };
+        let wayland = "WAYLAND_DISPLAY";
+        assert_eq!(pick(&[wayland, "SWAYSOCK"], &["swww", "feh"]), "Sway");
+        assert_eq!(
+            pick(&[wayland, "HYPRLAND_INSTANCE_SIGNATURE"], &["swww"]),
+            "hyprpaper"
+        );
+        assert_eq!(pick(&[wayland], &["wbg", "swww", "feh"]), "swww");
+        assert_eq!(pick(&[wayland], &["wbg", "feh"]), "wbg");
+        //X11 setters aren't used on Wayland, even when they're installed
+        assert_eq!(pick(&[wayland], &["xwallpaper"]), "Feh");
+        //nor are the compositors' own ones without Wayland
+        assert_eq!(pick(&["SWAYSOCK"], &["swww", "nitrogen"]), "nitrogen");
+        assert_eq!(pick(&[], &["nitrogen", "xwallpaper", "feh"]), "Feh");
+        assert_eq!(pick(&[], &["nitrogen", "xwallpaper"]), "xwallpaper");
+        #[cfg(feature = "x11")]
+        assert_eq!(pick(&[], &[]), "X11");
+        #[cfg(not(feature = "x11"))]
+        assert_eq!(pick(&[], &[]), "Feh");
+    }
+}
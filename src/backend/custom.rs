@@ -0,0 +1,121 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use super::{Backend, SetOptions};
+use crate::command::{run, split, start};
+use std::{
+    error::Error,
+    path::Path,
+    process::{Child, Command},
+    sync::Mutex,
+};
+
+//setters that keep running for as long as they show the image, rather than exiting once it's set
+const LONG_RUNNING: [&str; 1] = ["swaybg"];
+
+//the long running setter showing the last image, stopped once the next one has started
+static RUNNING: Mutex<Option<Child>> = Mutex::new(None);
+
+///A user specified command. `!WALL` is replaced with the image path wherever it appears, and if
+///it isn't present the path is added as the last argument. Setters that keep running, like
+///swaybg, are left running instead of being waited on
+pub struct Custom {
+    command: String,
+}
+
+impl Custom {
+    pub fn new(command: &str) -> Self {
+        Custom {
+            command: command.to_string(),
+        }
+    }
+}
+
+impl Backend for Custom {
+    fn name(&self) -> &str {
+        &self.command
+    }
+
+    fn detect(&self, desktop: &str) -> bool {
+        desktop == self.command
+    }
+
+    fn set(&self, filepath: &str, _options: &SetOptions) -> Result<(), Box<dyn Error>> {
+        let mut backend_split = split(&self.command)?.into_iter();
+        let mut cust_handle = Command::new(backend_split.next().unwrap());
+        let mut wall_sent = false;
+        for word in backend_split {
+            wall_sent |= word.contains("!WALL");
+            cust_handle.arg(word.replace("!WALL", filepath));
+        }
+
+        if !wall_sent {
+            cust_handle.arg(filepath);
+        }
+
+        let program = Path::new(cust_handle.get_program()).file_name();
+        if !LONG_RUNNING
+            .iter()
+            .any(|name| program == Some(name.as_ref()))
+        {
+            run(&mut cust_handle, &self.command)?;
+            return Ok(());
+        }
+        let child = start(&mut cust_handle, &self.command)?;
+        //stopped after the new one has started, so the desktop isn't left without a wallpaper
+        if let Some(mut old) = RUNNING.lock().expect("Setter poisoned").replace(child) {
+            let _ = old.kill();
+            let _ = old.wait();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, os::unix::fs::PermissionsExt, time::Instant};
+
+    #[test]
+    fn swaybg_is_left_running_until_the_next_one_starts() {
+        let dir = env::temp_dir().join(format!("dyn-wall-rs-swaybg-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let swaybg = dir.join("swaybg");
+        fs::write(&swaybg, "#!/bin/sh\nexec sleep 60\n").unwrap();
+        fs::set_permissions(&swaybg, fs::Permissions::from_mode(0o755)).unwrap();
+        let backend = Custom::new(&format!("{} -m fill -i !WALL", swaybg.display()));
+        let running = || RUNNING.lock().unwrap().as_ref().map(Child::id);
+
+        let started = Instant::now();
+        backend.set("/1.png", &SetOptions::default()).unwrap();
+        let first = running().unwrap();
+        backend.set("/2.png", &SetOptions::default()).unwrap();
+        let second = running().unwrap();
+        assert!(started.elapsed().as_secs() < 10);
+        assert_ne!(first, second);
+        //the first one has been stopped and waited on
+        assert!(!Path::new(&format!("/proc/{}", first)).exists());
+
+        if let Some(mut last) = RUNNING.lock().unwrap().take() {
+            let _ = last.kill();
+            let _ = last.wait();
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,507 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use super::{Backend, Mode, SetOptions};
+use crate::{
+    command::{output, run},
+    errors::Errors,
+};
+use std::{env::consts::ARCH, error::Error, path::Path, process::Command, sync::Mutex};
+use unicase::UniCase;
+
+fn matches_any(desktop: &str, names: &[&str]) -> bool {
+    let desktop = UniCase::new(desktop);
+    names.iter().any(|name| UniCase::new(*name) == desktop)
+}
+
+//the picture-options value gnome (and mate, which has the same setting) uses for each mode
+fn picture_options(mode: Mode) -> &'static str {
+    match mode {
+        Mode::Fill => "zoom",
+        Mode::Fit => "scaled",
+        Mode::Center => "centered",
+        Mode::Tile => "wallpaper",
+        Mode::Stretch => "stretched",
+        Mode::Span => "spanned",
+    }
+}
+
+//sets picture-options in the schema if a mode was asked for, otherwise it's left as it is
+fn set_picture_options(schema: &str, options: &SetOptions, name: &str) -> Result<(), Errors> {
+    if let Some(mode) = options.mode {
+        run(
+            Command::new("gsettings").args([
+                "set",
+                schema,
+                "picture-options",
+                picture_options(mode),
+            ]),
+            name,
+        )?;
+    }
+    Ok(())
+}
+
+//whether gnome has picture-uri-dark, found out the first time it's needed
+static DARK_KEY: Mutex<Option<bool>> = Mutex::new(None);
+
+//gsettings prints strings quoted and escaped, and uris with the file:// scheme
+fn gsettings_get(schema: &str, key: &str) -> Option<String> {
+    let value = output(
+        Command::new("gsettings").arg("get").arg(schema).arg(key),
+        "gsettings",
+    )
+    .ok()?;
+    let value = unquote(value.trim());
+    Some(value.trim_start_matches("file://").to_string())
+}
+
+//writes a string the way gsettings reads it, in single quotes with quotes and backslashes escaped
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+//reads a string the way gsettings prints it, in single quotes, or double quotes if it has a single
+//quote in it
+fn unquote(value: &str) -> String {
+    let inner = ['\'', '"']
+        .iter()
+        .find_map(|quote| value.strip_prefix(*quote)?.strip_suffix(*quote))
+        .unwrap_or(value);
+    let mut unquoted = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+//gnome 42 and later have picture-uri-dark. It's only looked up once gsettings says for sure, so a
+//session that isn't ready yet is asked again next time
+fn has_dark_key() -> bool {
+    let mut known = DARK_KEY.lock().expect("Dark key poisoned");
+    if let Some(known) = *known {
+        return known;
+    }
+    let found = output(
+        Command::new("gsettings").args(["get", "org.gnome.desktop.background", "picture-uri-dark"]),
+        "gsettings",
+    );
+    match found {
+        Ok(_) => *known = Some(true),
+        Err(Errors::CommandFailedError(_, _, stderr)) if stderr.contains("No such key") => {
+            *known = Some(false)
+        }
+        Err(_) => {}
+    }
+    known.unwrap_or(false)
+}
+
+//the image shown with the dark style: for an image in a day directory, the image with the same
+//name in the night directory next to it if there is one, otherwise the image itself
+fn dark_variant(filepath: &str) -> String {
+    let path = Path::new(filepath);
+    let in_day = path
+        .parent()
+        .and_then(Path::file_name)
+        .is_some_and(|dir| dir == "day");
+    let night = path
+        .parent()
+        .and_then(Path::parent)
+        .zip(path.file_name())
+        .map(|(base, name)| base.join("night").join(name));
+    match night {
+        Some(night) if in_day && night.is_file() => night.to_string_lossy().into_owned(),
+        _ => filepath.to_string(),
+    }
+}
+
+///Gnome, Ubuntu, Deepin, Pop
+pub struct Gnome;
+
+impl Backend for Gnome {
+    fn name(&self) -> &str {
+        "Gnome"
+    }
+
+    fn detect(&self, desktop: &str) -> bool {
+        matches_any(
+            desktop,
+            &[
+                "gnome",
+                "gnome-xorg",
+                "ubuntu",
+                "deepin",
+                "pop",
+                "ubuntu:gnome",
+            ],
+        )
+    }
+
+    fn set(&self, filepath: &str, options: &SetOptions) -> Result<(), Box<dyn Error>> {
+        let set_uri = |schema: &str, key: &str, filepath: &str| {
+            run(
+                Command::new("gsettings")
+                    .arg("set")
+                    .arg(schema)
+                    .arg(key)
+                    .arg(quote(&format!("file://{}", filepath))),
+                "Gnome Wallpaper Adjuster",
+            )
+        };
+        set_uri("org.gnome.desktop.background", "picture-uri", filepath)?;
+        //gnome 42 and later show picture-uri-dark instead when the dark style is on
+        if has_dark_key() {
+            let dark = if options.dark_night {
+                dark_variant(filepath)
+            } else {
+                filepath.to_string()
+            };
+            set_uri("org.gnome.desktop.background", "picture-uri-dark", &dark)?;
+        }
+        set_uri("org.gnome.desktop.screensaver", "picture-uri", filepath)?;
+        set_picture_options(
+            "org.gnome.desktop.background",
+            options,
+            "Gnome Wallpaper Adjuster",
+        )?;
+        Ok(())
+    }
+
+    fn current(&self) -> Option<String> {
+        gsettings_get("org.gnome.desktop.background", "picture-uri")
+    }
+}
+
+pub struct Pantheon;
+
+impl Backend for Pantheon {
+    fn name(&self) -> &str {
+        "Pantheon"
+    }
+
+    fn detect(&self, desktop: &str) -> bool {
+        matches_any(desktop, &["pantheon"])
+    }
+
+    fn set(&self, filepath: &str, options: &SetOptions) -> Result<(), Box<dyn Error>> {
+        let multiarch_dir = format!("/usr/lib/{}-linux-gnu/", ARCH);
+        run(
+            Command::new(multiarch_dir + "io.elementary.contract.set-wallpaper").arg(filepath),
+            "Pantheon Wallpaper Adjuster",
+        )?;
+        //pantheon uses gnome's settings for the background
+        set_picture_options(
+            "org.gnome.desktop.background",
+            options,
+            "Pantheon Wallpaper Adjuster",
+        )?;
+        Ok(())
+    }
+}
+
+pub struct Mate;
+
+impl Backend for Mate {
+    fn name(&self) -> &str {
+        "Mate"
+    }
+
+    fn detect(&self, desktop: &str) -> bool {
+        matches_any(desktop, &["mate"])
+    }
+
+    fn set(&self, filepath: &str, options: &SetOptions) -> Result<(), Box<dyn Error>> {
+        run(
+            Command::new("gsettings")
+                .arg("set")
+                .arg("org.mate.background")
+                .arg("picture-filename")
+                .arg(filepath),
+            "Mate Wallpaper Adjuster",
+        )?;
+        set_picture_options("org.mate.background", options, "Mate Wallpaper Adjuster")?;
+        Ok(())
+    }
+
+    fn current(&self) -> Option<String> {
+        gsettings_get("org.mate.background", "picture-filename")
+    }
+}
+
+pub struct Kde;
+
+impl Backend for Kde {
+    fn name(&self) -> &str {
+        "KDE"
+    }
+
+    fn detect(&self, desktop: &str) -> bool {
+        matches_any(
+            desktop,
+            &["plasma", "neon", "kde", "/usr/share/xsessions/plasma"],
+        )
+    }
+
+    fn set(&self, filepath: &str, options: &SetOptions) -> Result<(), Box<dyn Error>> {
+        run(
+            &mut kde_command(filepath, options),
+            "KDE Wallpaper Adjuster",
+        )?;
+        Ok(())
+    }
+}
+
+//the script is handed to the shell as an argument rather than pasted into it, and the path is
+//written into the script as a javascript string, so nothing in the path gets run
+fn kde_command(filepath: &str, options: &SetOptions) -> Command {
+    let kde_script_beg = r#"
+    var allDesktops = desktops();
+    print (allDesktops);
+    for (i=0;i<allDesktops.length;i++) {
+        d = allDesktops[i];
+        d.wallpaperPlugin = 'org.kde.image';
+        d.currentConfigGroup = Array('Wallpaper',
+                                    'org.kde.image',
+                                    'General');
+        d.writeConfig('Image', "#;
+    //the FillMode values are the ones of Qt's Image.fillMode, plasma has nothing for span
+    let fill_mode = options.mode.map(|mode| match mode {
+        Mode::Fill | Mode::Span => 2,
+        Mode::Fit => 1,
+        Mode::Center => 6,
+        Mode::Tile => 3,
+        Mode::Stretch => 0,
+    });
+    let fill_mode = fill_mode
+        .map(|fill_mode| format!("\n        d.writeConfig('FillMode', {});", fill_mode))
+        .unwrap_or_default();
+    let kde_script_end = r#"
+        }"#;
+    let uri = serde_json::Value::from(format!("file://{}", filepath));
+    let kde_script = format!("{}{});{}{}", kde_script_beg, uri, fill_mode, kde_script_end);
+
+    let mut kde = Command::new("sh");
+    kde.arg("-c")
+        .arg(r#"qdbus org.kde.plasmashell /PlasmaShell org.kde.PlasmaShell.evaluateScript "$1""#)
+        .arg("sh")
+        .arg(kde_script);
+    kde
+}
+
+pub struct Lxde;
+
+impl Backend for Lxde {
+    fn name(&self) -> &str {
+        "LXDE"
+    }
+
+    fn detect(&self, desktop: &str) -> bool {
+        matches_any(desktop, &["lxde"])
+    }
+
+    fn set(&self, filepath: &str, options: &SetOptions) -> Result<(), Box<dyn Error>> {
+        let mut pcmanfm = Command::new("pcmanfm");
+        pcmanfm.arg("--set-wallpaper").arg(filepath);
+        if let Some(mode) = options.mode {
+            let mode = match mode {
+                Mode::Fill => "crop",
+                Mode::Fit => "fit",
+                Mode::Center => "center",
+                Mode::Tile => "tile",
+                Mode::Stretch => "stretch",
+                Mode::Span => "screen",
+            };
+            pcmanfm.arg(format!("--wallpaper-mode={}", mode));
+        }
+        run(&mut pcmanfm, "LXDE Wallpaper Adjuster")?;
+        Ok(())
+    }
+}
+
+pub struct Xfce;
+
+impl Backend for Xfce {
+    fn name(&self) -> &str {
+        "XFCE"
+    }
+
+    fn detect(&self, desktop: &str) -> bool {
+        matches_any(desktop, &["xfce", "xubuntu", "xfce session"])
+    }
+
+    fn set(&self, filepath: &str, options: &SetOptions) -> Result<(), Box<dyn Error>> {
+        run(
+            &mut xfce_command(filepath, options),
+            "XFCE Wallpaper Adjuster",
+        )?;
+        Ok(())
+    }
+}
+
+//the path is handed to the shell as $1 rather than pasted into the script, so nothing in it gets
+//run
+fn xfce_command(filepath: &str, options: &SetOptions) -> Command {
+    //let xfce_script_beg = r#"xfconf-query -c xfce4-desktop \
+    //-p /backdrop/screen0/monitor0/workspace0/last-image \
+    //-s ""#;
+    let xfce_script_beg = "xfconf-query -c xfce4-desktop -l | grep last-image | while read path; do xfconf-query -c xfce4-desktop -p $path -s ";
+    let xfce_script_end = r#"; done"#;
+    let mut xfce_script = format!("{}{}{}", xfce_script_beg, r#""$1""#, xfce_script_end);
+    //image-style is set the same way as the image, for every monitor and workspace
+    if let Some(mode) = options.mode {
+        let style = match mode {
+            Mode::Fill => 5,
+            Mode::Fit => 4,
+            Mode::Center => 1,
+            Mode::Tile => 2,
+            Mode::Stretch => 3,
+            Mode::Span => 6,
+        };
+        xfce_script += &format!("; xfconf-query -c xfce4-desktop -l | grep image-style | while read path; do xfconf-query -c xfce4-desktop -p $path -s {}; done", style);
+    }
+
+    let mut xfce = Command::new("sh");
+    xfce.arg("-c").arg(xfce_script).arg("sh").arg(filepath);
+    xfce
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, os::unix::fs::PermissionsExt, path::PathBuf};
+
+    //a directory with fake programs that write the argument the value is passed in to `log`
+    fn fakes(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("dyn-wall-rs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let scripts = [
+            (
+                "xfconf-query",
+                r#"[ "$3" = -l ] && printf '%s\n' /backdrop/screen0/monitor0/workspace0/last-image /backdrop/screen0/monitor0/workspace0/image-style && exit
+printf '%s\n' "$6" >> "$(dirname "$0")/log""#,
+            ),
+            ("qdbus", r#"printf '%s\n' "$4" >> "$(dirname "$0")/log""#),
+        ];
+        for (program, script) in Vec::from(scripts) {
+            let path = dir.join(program);
+            fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        dir
+    }
+
+    //a path with spaces and everything the shell would otherwise act on
+    fn awkward_path(dir: &Path) -> String {
+        format!(
+            "{}/My Pictures/a \"b\" $(touch {0}/ran) `touch {0}/ran` 'c' \\ $HOME;.png",
+            dir.display()
+        )
+    }
+
+    fn run_with(command: &mut Command, dir: &Path) -> String {
+        let path = format!("{}:{}", dir.display(), env::var("PATH").unwrap_or_default());
+        run(command.env("PATH", path), "test").unwrap();
+        fs::read_to_string(dir.join("log")).unwrap()
+    }
+
+    #[test]
+    fn dark_variant_is_the_night_image_with_the_same_name() {
+        let dir = fakes("dark");
+        for image in ["day/1.png", "day/2.png", "night/1.png", "other/1.png"].iter() {
+            fs::create_dir_all(dir.join(image).parent().unwrap()).unwrap();
+            fs::write(dir.join(image), "").unwrap();
+        }
+        let variant = |image: &str| dark_variant(&dir.join(image).to_string_lossy());
+        assert_eq!(
+            variant("day/1.png"),
+            dir.join("night/1.png").to_string_lossy()
+        );
+        //no night image to go with it
+        assert_eq!(
+            variant("day/2.png"),
+            dir.join("day/2.png").to_string_lossy()
+        );
+        //not in a day directory
+        assert_eq!(
+            variant("other/1.png"),
+            dir.join("other/1.png").to_string_lossy()
+        );
+        assert_eq!(
+            variant("night/1.png"),
+            dir.join("night/1.png").to_string_lossy()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn gsettings_strings_are_escaped() {
+        let path = r"file:///home/me/it's a \ path.png";
+        assert_eq!(quote(path), r"'file:///home/me/it\'s a \\ path.png'");
+        assert_eq!(unquote(&quote(path)), path);
+        //gsettings prints strings with a single quote in double quotes
+        assert_eq!(
+            unquote(r#""file:///home/me/it's.png""#),
+            "file:///home/me/it's.png"
+        );
+        assert_eq!(unquote("'file:///1.png'"), "file:///1.png");
+    }
+
+    #[test]
+    fn xfce_passes_the_path_through_unchanged() {
+        let dir = fakes("xfce");
+        let filepath = awkward_path(&dir);
+        let options = SetOptions {
+            mode: Some(Mode::Fit),
+            ..SetOptions::default()
+        };
+        let log = run_with(&mut xfce_command(&filepath, &options), &dir);
+        assert_eq!(log, format!("{}\n4\n", filepath));
+        assert!(!dir.join("ran").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn xfce_leaves_the_style_alone_without_a_mode() {
+        let dir = fakes("xfce-style");
+        let log = run_with(&mut xfce_command("/1.png", &SetOptions::default()), &dir);
+        assert_eq!(log, "/1.png\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn kde_writes_the_path_as_a_string() {
+        let dir = fakes("kde");
+        let filepath = awkward_path(&dir);
+        let options = SetOptions {
+            mode: Some(Mode::Fill),
+            ..SetOptions::default()
+        };
+        let log = run_with(&mut kde_command(&filepath, &options), &dir);
+        let uri = serde_json::to_string(&format!("file://{}", filepath)).unwrap();
+        assert!(log.contains(&format!("d.writeConfig('Image', {});", uri)));
+        assert!(log.contains("d.writeConfig('FillMode', 2);"));
+        assert!(!dir.join("ran").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
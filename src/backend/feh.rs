@@ -0,0 +1,107 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use super::{Backend, Mode, SetOptions};
+use crate::command::run;
+use dirs_next::home_dir;
+use std::{error::Error, fs, process::Command};
+
+///Used for window managers, and whenever no desktop environment is detected
+pub struct Feh;
+
+impl Backend for Feh {
+    fn name(&self) -> &str {
+        "Feh"
+    }
+
+    fn detect(&self, desktop: &str) -> bool {
+        unicase::eq(desktop, "feh")
+    }
+
+    fn set(&self, filepath: &str, options: &SetOptions) -> Result<(), Box<dyn Error>> {
+        run(&mut feh_command(filepath, options), "Feh")?;
+        Ok(())
+    }
+
+    fn current(&self) -> Option<String> {
+        fehbg_wallpaper(&fs::read_to_string(home_dir()?.join(".fehbg")).ok()?)
+    }
+}
+
+//feh keeps the last command it ran in ~/.fehbg, with the image path as the last argument, quoted
+//for the shell
+fn fehbg_wallpaper(fehbg: &str) -> Option<String> {
+    let last_line = fehbg.lines().rev().find(|line| !line.trim().is_empty())?;
+    shell_words::split(last_line).ok()?.pop()
+}
+
+//feh has always stretched the image when no mode is given
+fn feh_command(filepath: &str, options: &SetOptions) -> Command {
+    let mut feh = Command::new("feh");
+    match options.mode {
+        None | Some(Mode::Stretch) => feh.arg("--bg-scale"),
+        Some(Mode::Fill) => feh.arg("--bg-fill"),
+        Some(Mode::Fit) => feh.arg("--bg-max"),
+        Some(Mode::Center) => feh.arg("--bg-center"),
+        Some(Mode::Tile) => feh.arg("--bg-tile"),
+        Some(Mode::Span) => feh.arg("--bg-fill").arg("--no-xinerama"),
+    };
+    feh.arg(filepath);
+    feh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(mode: Option<Mode>) -> Vec<String> {
+        let options = SetOptions {
+            mode,
+            ..SetOptions::default()
+        };
+        feh_command("/home/me/My Pictures/1.png", &options)
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn reads_quoted_paths_from_fehbg() {
+        let fehbg = "#!/bin/sh\nfeh --no-fehbg --bg-scale '/home/me/My Pictures/it'\\''s 1.png' \n";
+        assert_eq!(
+            fehbg_wallpaper(fehbg).as_deref(),
+            Some("/home/me/My Pictures/it's 1.png")
+        );
+        assert_eq!(fehbg_wallpaper("#!/bin/sh\n"), None);
+        assert_eq!(fehbg_wallpaper(""), None);
+    }
+
+    #[test]
+    fn stretches_unless_a_mode_is_given() {
+        assert_eq!(args(None), ["--bg-scale", "/home/me/My Pictures/1.png"]);
+        assert_eq!(
+            args(Some(Mode::Fill)),
+            ["--bg-fill", "/home/me/My Pictures/1.png"]
+        );
+        assert_eq!(
+            args(Some(Mode::Span)),
+            ["--bg-fill", "--no-xinerama", "/home/me/My Pictures/1.png"]
+        );
+    }
+}
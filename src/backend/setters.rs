@@ -0,0 +1,365 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//standalone programs for setting the wallpaper, for compositors and window managers that don't
+//have their own way
+use super::{Backend, Mode, SetOptions};
+use crate::command::{run, start};
+use dirs_next::config_dir;
+use std::{
+    error::Error,
+    fs,
+    process::{Child, Command},
+    sync::Mutex,
+};
+
+///swww, for wlroots based compositors. The transition options are passed on to it, and its
+///daemon is started if it isn't running yet
+pub struct Swww;
+
+impl Backend for Swww {
+    fn name(&self) -> &str {
+        "swww"
+    }
+
+    fn detect(&self, desktop: &str) -> bool {
+        unicase::eq(desktop, "swww")
+    }
+
+    fn set(&self, filepath: &str, options: &SetOptions) -> Result<(), Box<dyn Error>> {
+        if run(Command::new("swww").arg("query"), "swww").is_err() {
+            start(&mut Command::new("swww-daemon"), "swww-daemon")?;
+        }
+
+        run(&mut swww_command(filepath, options), "swww")?;
+        Ok(())
+    }
+}
+
+fn swww_command(filepath: &str, options: &SetOptions) -> Command {
+    let mut swww = Command::new("swww");
+    swww.arg("img").arg(filepath);
+    let monitors = options.monitors();
+    if !monitors.is_empty() {
+        swww.arg("--outputs").arg(monitors.join(","));
+    }
+    if let Some(transition) = &options.transition {
+        swww.arg("--transition-type").arg(transition);
+    }
+    if let Some(duration) = options.transition_duration {
+        swww.arg("--transition-duration").arg(duration.to_string());
+    }
+    //swww can't tile or span, so those are left to its default of filling
+    let resize = match options.mode {
+        Some(Mode::Fill) => Some("crop"),
+        Some(Mode::Fit) => Some("fit"),
+        Some(Mode::Center) => Some("no"),
+        Some(Mode::Stretch) => Some("stretch"),
+        _ => None,
+    };
+    if let Some(resize) = resize {
+        swww.arg("--resize").arg(resize);
+    }
+    swww
+}
+
+///hyprpaper, for Hyprland, controlled through hyprctl
+pub struct Hyprpaper;
+
+impl Backend for Hyprpaper {
+    fn name(&self) -> &str {
+        "hyprpaper"
+    }
+
+    fn detect(&self, desktop: &str) -> bool {
+        unicase::eq(desktop, "hyprpaper") || unicase::eq(desktop, "hyprland")
+    }
+
+    fn set(&self, filepath: &str, options: &SetOptions) -> Result<(), Box<dyn Error>> {
+        for mut hyprctl in hyprpaper_commands(filepath, options) {
+            run(&mut hyprctl, "hyprpaper")?;
+        }
+        Ok(())
+    }
+}
+
+//the image is preloaded, shown on each monitor, and then images that aren't shown anymore are
+//dropped, so they don't build up in memory
+fn hyprpaper_commands(filepath: &str, options: &SetOptions) -> Vec<Command> {
+    let hyprctl = |args: [&str; 3]| {
+        let mut hyprctl = Command::new("hyprctl");
+        hyprctl.args(args);
+        hyprctl
+    };
+    //hyprpaper fills the monitor unless told to fit (contain) or tile
+    let mode = match options.mode {
+        Some(Mode::Fit) => "contain:",
+        Some(Mode::Tile) => "tile:",
+        _ => "",
+    };
+    //an empty monitor sets all of them
+    let monitors = match options.monitors() {
+        monitors if monitors.is_empty() => vec![""],
+        monitors => monitors,
+    };
+
+    let mut commands = vec![hyprctl(["hyprpaper", "preload", filepath])];
+    for monitor in monitors {
+        let wallpaper = format!("{},{}{}", monitor, mode, filepath);
+        commands.push(hyprctl(["hyprpaper", "wallpaper", &wallpaper]));
+    }
+    commands.push(hyprctl(["hyprpaper", "unload", "unused"]));
+    commands
+}
+
+//wbg keeps running for as long as it shows the image, so the one showing the last image is kept
+//to be stopped once the next one has started
+static WBG: Mutex<Option<Child>> = Mutex::new(None);
+
+///wbg, for wlroots based compositors
+pub struct Wbg;
+
+impl Backend for Wbg {
+    fn name(&self) -> &str {
+        "wbg"
+    }
+
+    fn detect(&self, desktop: &str) -> bool {
+        unicase::eq(desktop, "wbg")
+    }
+
+    fn set(&self, filepath: &str, _options: &SetOptions) -> Result<(), Box<dyn Error>> {
+        let wbg = start(Command::new("wbg").arg(filepath), "wbg")?;
+        //stopped after the new one has started, so the desktop isn't left without a wallpaper
+        if let Some(mut old) = WBG.lock().expect("wbg poisoned").replace(wbg) {
+            let _ = old.kill();
+            let _ = old.wait();
+        }
+        Ok(())
+    }
+}
+
+///xwallpaper, for X11 window managers
+pub struct Xwallpaper;
+
+impl Backend for Xwallpaper {
+    fn name(&self) -> &str {
+        "xwallpaper"
+    }
+
+    fn detect(&self, desktop: &str) -> bool {
+        unicase::eq(desktop, "xwallpaper")
+    }
+
+    fn set(&self, filepath: &str, options: &SetOptions) -> Result<(), Box<dyn Error>> {
+        run(&mut xwallpaper_command(filepath, options), "xwallpaper")?;
+        Ok(())
+    }
+}
+
+fn xwallpaper_command(filepath: &str, options: &SetOptions) -> Command {
+    let mut xwallpaper = Command::new("xwallpaper");
+    let mode = match options.mode.unwrap_or_default() {
+        Mode::Fill => "--zoom",
+        Mode::Fit => "--maximize",
+        Mode::Center => "--center",
+        Mode::Tile => "--tile",
+        Mode::Stretch => "--stretch",
+        Mode::Span => {
+            xwallpaper.arg("--no-randr");
+            "--zoom"
+        }
+    };
+    //each output is given its own mode and image
+    let monitors = options.monitors();
+    if monitors.is_empty() {
+        xwallpaper.arg(mode).arg(filepath);
+    }
+    for monitor in monitors {
+        xwallpaper
+            .arg("--output")
+            .arg(monitor)
+            .arg(mode)
+            .arg(filepath);
+    }
+    xwallpaper
+}
+
+///nitrogen, for X11 window managers
+pub struct Nitrogen;
+
+impl Backend for Nitrogen {
+    fn name(&self) -> &str {
+        "nitrogen"
+    }
+
+    fn detect(&self, desktop: &str) -> bool {
+        unicase::eq(desktop, "nitrogen")
+    }
+
+    fn set(&self, filepath: &str, options: &SetOptions) -> Result<(), Box<dyn Error>> {
+        run(&mut nitrogen_command(filepath, options), "nitrogen")?;
+        Ok(())
+    }
+
+    //nitrogen saves the image it set with --save in its config
+    fn current(&self) -> Option<String> {
+        let saved = fs::read_to_string(config_dir()?.join("nitrogen").join("bg-saved.cfg")).ok()?;
+        let file = saved.lines().find_map(|line| line.strip_prefix("file="))?;
+        Some(file.to_string())
+    }
+}
+
+//nitrogen has nothing for span, so each monitor is filled
+fn nitrogen_command(filepath: &str, options: &SetOptions) -> Command {
+    let mode = match options.mode.unwrap_or_default() {
+        Mode::Fill | Mode::Span => "--set-zoom-fill",
+        Mode::Fit => "--set-zoom",
+        Mode::Center => "--set-centered",
+        Mode::Tile => "--set-tiled",
+        Mode::Stretch => "--set-scaled",
+    };
+    let mut nitrogen = Command::new("nitrogen");
+    nitrogen.arg(mode).arg("--save").arg(filepath);
+    nitrogen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMAGE: &str = "/home/me/My Pictures/1.png";
+
+    fn options(mode: Option<Mode>, monitor: Option<&str>) -> SetOptions {
+        SetOptions {
+            mode,
+            monitor: monitor.map(String::from),
+            ..SetOptions::default()
+        }
+    }
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn swww_gets_the_transition_and_outputs() {
+        assert_eq!(
+            args(&swww_command(IMAGE, &options(None, None))),
+            ["img", IMAGE]
+        );
+
+        let set = SetOptions {
+            transition: Some(String::from("wipe")),
+            transition_duration: Some(1.5),
+            ..options(Some(Mode::Fit), Some("DP-1, HDMI-A-1"))
+        };
+        assert_eq!(
+            args(&swww_command(IMAGE, &set)),
+            [
+                "img",
+                IMAGE,
+                "--outputs",
+                "DP-1,HDMI-A-1",
+                "--transition-type",
+                "wipe",
+                "--transition-duration",
+                "1.5",
+                "--resize",
+                "fit"
+            ]
+        );
+        //there's nothing to tile with, so swww fills as usual
+        assert_eq!(
+            args(&swww_command(IMAGE, &options(Some(Mode::Tile), None))),
+            ["img", IMAGE]
+        );
+    }
+
+    #[test]
+    fn hyprpaper_sets_each_monitor() {
+        let commands = |options: &SetOptions| -> Vec<Vec<String>> {
+            hyprpaper_commands(IMAGE, options)
+                .iter()
+                .map(args)
+                .collect()
+        };
+        let hyprctl = |command: &str, arg: &str| -> Vec<String> {
+            Vec::from(["hyprpaper", command, arg])
+                .into_iter()
+                .map(String::from)
+                .collect()
+        };
+        let preload = hyprctl("preload", IMAGE);
+        let unload = hyprctl("unload", "unused");
+
+        assert_eq!(
+            commands(&options(None, None)),
+            [
+                preload.clone(),
+                hyprctl("wallpaper", &format!(",{}", IMAGE)),
+                unload.clone()
+            ]
+        );
+        assert_eq!(
+            commands(&options(Some(Mode::Fit), Some("DP-1,HDMI-A-1"))),
+            [
+                preload,
+                hyprctl("wallpaper", &format!("DP-1,contain:{}", IMAGE)),
+                hyprctl("wallpaper", &format!("HDMI-A-1,contain:{}", IMAGE)),
+                unload
+            ]
+        );
+        assert!(commands(&options(Some(Mode::Tile), None))[1][2].starts_with(",tile:"));
+        assert!(commands(&options(Some(Mode::Center), None))[1][2].starts_with(",/"));
+    }
+
+    #[test]
+    fn xwallpaper_gives_each_output_its_mode() {
+        let xwallpaper = |mode, monitor| args(&xwallpaper_command(IMAGE, &options(mode, monitor)));
+        assert_eq!(xwallpaper(None, None), ["--zoom", IMAGE]);
+        assert_eq!(xwallpaper(Some(Mode::Tile), None), ["--tile", IMAGE]);
+        assert_eq!(
+            xwallpaper(Some(Mode::Span), None),
+            ["--no-randr", "--zoom", IMAGE]
+        );
+        assert_eq!(
+            xwallpaper(Some(Mode::Center), Some("DP-1,HDMI-1")),
+            ["--output", "DP-1", "--center", IMAGE, "--output", "HDMI-1", "--center", IMAGE]
+        );
+    }
+
+    #[test]
+    fn nitrogen_saves_the_image() {
+        let nitrogen = |mode| args(&nitrogen_command(IMAGE, &options(mode, None)));
+        assert_eq!(nitrogen(None), ["--set-zoom-fill", "--save", IMAGE]);
+        assert_eq!(
+            nitrogen(Some(Mode::Span)),
+            ["--set-zoom-fill", "--save", IMAGE]
+        );
+        assert_eq!(nitrogen(Some(Mode::Fit)), ["--set-zoom", "--save", IMAGE]);
+        assert_eq!(
+            nitrogen(Some(Mode::Stretch)),
+            ["--set-scaled", "--save", IMAGE]
+        );
+    }
+}
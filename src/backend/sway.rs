@@ -0,0 +1,239 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use super::{Backend, Mode, SetOptions};
+use crate::errors::Errors;
+use serde::Deserialize;
+use std::{
+    env,
+    error::Error,
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    time::Duration,
+};
+
+//every message to and from sway starts with this, followed by the length of the payload and the
+//message type
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const RUN_COMMAND: u32 = 0;
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+///Sets the background of sway's outputs through its IPC socket, found through `SWAYSOCK`
+pub struct Sway;
+
+//sway replies to a command with one of these for each command it ran
+#[derive(Deserialize)]
+struct CommandResult {
+    success: bool,
+    error: Option<String>,
+}
+
+//the results usually come as a list, but a command that can't be run at all may get just the one
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Reply {
+    List(Vec<CommandResult>),
+    Single(CommandResult),
+}
+
+impl Backend for Sway {
+    fn name(&self) -> &str {
+        "Sway"
+    }
+
+    fn detect(&self, desktop: &str) -> bool {
+        unicase::eq(desktop, "sway")
+    }
+
+    fn set(&self, filepath: &str, options: &SetOptions) -> Result<(), Box<dyn Error>> {
+        let outputs = match options.monitors() {
+            outputs if outputs.is_empty() => vec!["*"],
+            outputs => outputs,
+        };
+        //sway has nothing for span, so each output is filled
+        let mode = match options.mode.unwrap_or_default() {
+            Mode::Fill | Mode::Span => "fill",
+            Mode::Fit => "fit",
+            Mode::Center => "center",
+            Mode::Tile => "tile",
+            Mode::Stretch => "stretch",
+        };
+        //one command for each output, which sway runs one after the other
+        let command = outputs
+            .iter()
+            .map(|output| format!("output {} bg {} {}", quote(output), quote(filepath), mode))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let results = match serde_json::from_slice(&run_command(&command)?) {
+            Ok(Reply::List(results)) => results,
+            Ok(Reply::Single(result)) => vec![result],
+            Err(e) => {
+                return Err(Errors::IpcError(format!("unable to read the reply: {}", e)).into())
+            }
+        };
+        match results.into_iter().find(|result| !result.success) {
+            Some(failed) => Err(Errors::IpcError(failed.error.unwrap_or(command)).into()),
+            None => Ok(()),
+        }
+    }
+}
+
+//sends a command to sway, returning the payload of the reply
+fn run_command(command: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let socket = env::var("SWAYSOCK")
+        .map_err(|_| Errors::IpcError(String::from("SWAYSOCK isn't set, is sway running?")))?;
+    let mut stream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+
+    let mut message = MAGIC.to_vec();
+    message.extend((command.len() as u32).to_ne_bytes());
+    message.extend(RUN_COMMAND.to_ne_bytes());
+    message.extend(command.as_bytes());
+    stream.write_all(&message)?;
+
+    let mut header = [0; 14];
+    stream.read_exact(&mut header)?;
+    if &header[..6] != MAGIC {
+        return Err(Errors::IpcError(String::from("the reply isn't an IPC message")).into());
+    }
+    let mut len = [0; 4];
+    len.copy_from_slice(&header[6..10]);
+    let mut payload = vec![0; u32::from_ne_bytes(len) as usize];
+    stream.read_exact(&mut payload)?;
+    Ok(payload)
+}
+
+//wraps an argument in quotes for sway's command parser, so paths with spaces work
+fn quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        os::unix::net::UnixListener,
+        path::PathBuf,
+        process,
+        sync::Mutex,
+        thread::{self, JoinHandle},
+    };
+
+    //SWAYSOCK is shared by the whole process, so only one test can point it somewhere at a time
+    static SWAYSOCK: Mutex<()> = Mutex::new(());
+
+    //a socket that answers one message with `reply`, handing back the header and payload it got
+    fn mock_sway(name: &str, reply: &'static str) -> (PathBuf, JoinHandle<(Vec<u8>, String)>) {
+        let path = env::temp_dir().join(format!("dyn-wall-rs-{}-{}.sock", process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut header = vec![0; 14];
+            stream.read_exact(&mut header).unwrap();
+            let mut len = [0; 4];
+            len.copy_from_slice(&header[6..10]);
+            let mut payload = vec![0; u32::from_ne_bytes(len) as usize];
+            stream.read_exact(&mut payload).unwrap();
+
+            let mut message = MAGIC.to_vec();
+            message.extend((reply.len() as u32).to_ne_bytes());
+            message.extend(RUN_COMMAND.to_ne_bytes());
+            message.extend(reply.as_bytes());
+            stream.write_all(&message).unwrap();
+            (header, String::from_utf8(payload).unwrap())
+        });
+        (path, server)
+    }
+
+    fn set_through(name: &str, reply: &'static str) -> (Result<(), String>, Vec<u8>, String) {
+        set_on(name, None, reply)
+    }
+
+    fn set_on(
+        name: &str,
+        monitor: Option<&str>,
+        reply: &'static str,
+    ) -> (Result<(), String>, Vec<u8>, String) {
+        let _lock = SWAYSOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let (path, server) = mock_sway(name, reply);
+        env::set_var("SWAYSOCK", &path);
+        let options = SetOptions {
+            monitor: monitor.map(String::from),
+            ..SetOptions::default()
+        };
+        let result = Sway
+            .set("/home/me/My Pictures/1.png", &options)
+            .map_err(|e| e.to_string());
+        let (header, payload) = server.join().unwrap();
+        let _ = std::fs::remove_file(&path);
+        (result, header, payload)
+    }
+
+    #[test]
+    fn sends_run_command_with_quoted_path() {
+        let (result, header, payload) = set_through("success", r#"[{"success":true}]"#);
+        assert_eq!(result, Ok(()));
+        assert_eq!(&header[..6], MAGIC);
+        assert_eq!(header[10..14], RUN_COMMAND.to_ne_bytes());
+        assert_eq!(header[6..10], (payload.len() as u32).to_ne_bytes());
+        assert_eq!(
+            payload,
+            r#"output "*" bg "/home/me/My Pictures/1.png" fill"#
+        );
+    }
+
+    #[test]
+    fn sets_each_output_given() {
+        let (result, _, payload) = set_on(
+            "outputs",
+            Some("HDMI-A-1, DP-2"),
+            r#"[{"success":true},{"success":true}]"#,
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            payload,
+            r#"output "HDMI-A-1" bg "/home/me/My Pictures/1.png" fill; output "DP-2" bg "/home/me/My Pictures/1.png" fill"#
+        );
+
+        let (result, _, _) = set_on(
+            "one-output-fails",
+            Some("HDMI-A-1,DP-3"),
+            r#"[{"success":true},{"success":false,"error":"Unknown output DP-3"}]"#,
+        );
+        assert_eq!(
+            result,
+            Err(String::from("Error from sway: Unknown output DP-3"))
+        );
+    }
+
+    #[test]
+    fn reports_sway_errors() {
+        let (result, _, _) = set_through(
+            "failure",
+            r#"{"success":false,"error":"Unable to access background file"}"#,
+        );
+        assert_eq!(
+            result,
+            Err(String::from(
+                "Error from sway: Unable to access background file"
+            ))
+        );
+    }
+}
@@ -0,0 +1,107 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use super::{Backend, Mode, SetOptions};
+use std::{error::Error, ffi::OsStr, io, iter, os::raw::c_void, os::windows::ffi::OsStrExt};
+use winapi::{
+    shared::minwindef::LPCVOID,
+    um::{
+        winnt::REG_SZ,
+        winreg::{RegSetKeyValueW, HKEY_CURRENT_USER},
+        winuser::{
+            SystemParametersInfoW, SPIF_SENDCHANGE, SPIF_UPDATEINIFILE, SPI_SETDESKWALLPAPER,
+        },
+    },
+};
+
+pub struct Windows;
+
+impl Backend for Windows {
+    fn name(&self) -> &str {
+        "Windows"
+    }
+
+    fn detect(&self, desktop: &str) -> bool {
+        unicase::eq(desktop, "windows")
+    }
+
+    fn set(&self, filepath: &str, options: &SetOptions) -> Result<(), Box<dyn Error>> {
+        if let Some(mode) = options.mode {
+            set_style(mode)?;
+        }
+        unsafe {
+            let file = OsStr::new(filepath)
+                .encode_wide()
+                // append null byte
+                .chain(iter::once(0))
+                .collect::<Vec<u16>>();
+            let successful = SystemParametersInfoW(
+                SPI_SETDESKWALLPAPER,
+                0,
+                file.as_ptr() as *mut c_void,
+                SPIF_UPDATEINIFILE | SPIF_SENDCHANGE,
+            ) == 1;
+
+            if successful {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error().into())
+            }
+        }
+    }
+}
+
+//windows reads how the wallpaper is fitted from the registry when it's set
+fn set_style(mode: Mode) -> Result<(), io::Error> {
+    let (style, tile) = match mode {
+        Mode::Fill => ("10", "0"),
+        Mode::Fit => ("6", "0"),
+        Mode::Center => ("0", "0"),
+        Mode::Tile => ("0", "1"),
+        Mode::Stretch => ("2", "0"),
+        Mode::Span => ("22", "0"),
+    };
+    let key = wide("Control Panel\\Desktop");
+    for (name, value) in [("WallpaperStyle", style), ("TileWallpaper", tile)].iter() {
+        let name = wide(name);
+        let value = wide(value);
+        let status = unsafe {
+            RegSetKeyValueW(
+                HKEY_CURRENT_USER,
+                key.as_ptr(),
+                name.as_ptr(),
+                REG_SZ,
+                value.as_ptr() as LPCVOID,
+                (value.len() * 2) as u32,
+            )
+        };
+        if status != 0 {
+            return Err(io::Error::from_raw_os_error(status));
+        }
+    }
+    Ok(())
+}
+
+//windows strings are null terminated utf-16
+fn wide(string: &str) -> Vec<u16> {
+    OsStr::new(string)
+        .encode_wide()
+        .chain(iter::once(0))
+        .collect()
+}
@@ -0,0 +1,298 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use super::{Backend, Mode, SetOptions};
+use crate::errors::Errors;
+use image::{
+    imageops::{self, FilterType},
+    DynamicImage, Rgba, RgbaImage,
+};
+use std::error::Error;
+use x11rb::{
+    connection::{Connection, RequestConnection},
+    protocol::xproto::{
+        AtomEnum, ChangeWindowAttributesAux, CloseDown, ConnectionExt, CreateGCAux, ImageFormat,
+        ImageOrder, PropMode, Window,
+    },
+    wrapper::ConnectionExt as _,
+};
+
+//the properties other programs (like compositors and terminals with fake transparency) read the
+//background from, as set by Esetroot, feh and hsetroot
+const ROOT_PIXMAP_ATOMS: [&[u8]; 2] = [b"_XROOTPMAP_ID", b"ESETROOT_PMAP_ID"];
+
+///Paints the image straight onto the X11 root window, without needing another program
+pub struct X11;
+
+impl Backend for X11 {
+    fn name(&self) -> &str {
+        "X11"
+    }
+
+    fn detect(&self, desktop: &str) -> bool {
+        unicase::eq(desktop, "x11")
+    }
+
+    fn set(&self, filepath: &str, options: &SetOptions) -> Result<(), Box<dyn Error>> {
+        let image = image::open(filepath)
+            .map_err(|e| Errors::X11Error(format!("unable to read {}: {}", filepath, e)))?;
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let screen = &conn.setup().roots[screen_num];
+        let (root, depth) = (screen.root, screen.root_depth);
+        let (width, height) = (screen.width_in_pixels, screen.height_in_pixels);
+        let canvas = fit(
+            image,
+            width.into(),
+            height.into(),
+            options.mode.unwrap_or_default(),
+        );
+
+        //only 32 bits per pixel is handled, which is what 24 and 32 bit displays use
+        let format = conn
+            .setup()
+            .pixmap_formats
+            .iter()
+            .find(|format| format.depth == depth);
+        if format.map(|format| format.bits_per_pixel) != Some(32) {
+            return Err(Errors::X11Error(format!("a depth of {} isn't supported", depth)).into());
+        }
+        let msb_first = conn.setup().image_byte_order == ImageOrder::MSB_FIRST;
+        let data: Vec<u8> = canvas
+            .pixels()
+            .flat_map(|Rgba([r, g, b, _])| {
+                let pixel = u32::from_be_bytes([0, *r, *g, *b]);
+                if msb_first {
+                    pixel.to_be_bytes()
+                } else {
+                    pixel.to_le_bytes()
+                }
+            })
+            .collect();
+
+        let pixmap = conn.generate_id()?;
+        conn.create_pixmap(depth, pixmap, root, width, height)?;
+        let gc = conn.generate_id()?;
+        conn.create_gc(gc, pixmap, &CreateGCAux::new())?;
+        //sent a few rows at a time, since the whole image is usually bigger than one request
+        let row_bytes = usize::from(width) * 4;
+        let rows = ((conn.maximum_request_bytes() - 64) / row_bytes).max(1);
+        for (chunk, bytes) in data.chunks(rows * row_bytes).enumerate() {
+            conn.put_image(
+                ImageFormat::Z_PIXMAP,
+                pixmap,
+                gc,
+                width,
+                (bytes.len() / row_bytes) as u16,
+                0,
+                (chunk * rows) as i16,
+                0,
+                depth,
+                bytes,
+            )?;
+        }
+        conn.free_gc(gc)?;
+
+        release_old_pixmap(&conn, root)?;
+        for name in ROOT_PIXMAP_ATOMS.iter() {
+            let atom = conn.intern_atom(false, name)?.reply()?.atom;
+            conn.change_property32(PropMode::REPLACE, root, atom, AtomEnum::PIXMAP, &[pixmap])?;
+        }
+        conn.change_window_attributes(
+            root,
+            &ChangeWindowAttributesAux::new().background_pixmap(pixmap),
+        )?;
+        conn.clear_area(false, root, 0, 0, 0, 0)?;
+        //the pixmap has to outlive this connection to stay as the background, so it's freed by
+        //the next change instead
+        conn.set_close_down_mode(CloseDown::RETAIN_PERMANENT)?;
+        conn.get_input_focus()?.reply()?;
+        Ok(())
+    }
+}
+
+//the last program to set the background kept its pixmap around after it disconnected, so it's
+//freed now that it's being replaced. Only done when both properties agree, like Esetroot does,
+//so a pixmap that belongs to something else isn't touched
+fn release_old_pixmap(conn: &impl Connection, root: Window) -> Result<(), Box<dyn Error>> {
+    let mut pixmaps = vec![];
+    for name in ROOT_PIXMAP_ATOMS.iter() {
+        let atom = conn.intern_atom(false, name)?.reply()?.atom;
+        let property = conn
+            .get_property(false, root, atom, AtomEnum::PIXMAP, 0, 1)?
+            .reply()?;
+        pixmaps.push(property.value32().and_then(|mut value| value.next()));
+    }
+    if let [Some(xroot), Some(esetroot)] = pixmaps[..] {
+        if xroot == esetroot {
+            conn.kill_client(xroot)?;
+        }
+    }
+    Ok(())
+}
+
+//scales and places the image on a screen sized canvas, with black where it isn't covered
+fn fit(image: DynamicImage, width: u32, height: u32, mode: Mode) -> RgbaImage {
+    let mut canvas = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+    let centered = |canvas: &mut RgbaImage, image: &RgbaImage| {
+        let x = (i64::from(width) - i64::from(image.width())) / 2;
+        let y = (i64::from(height) - i64::from(image.height())) / 2;
+        imageops::overlay(canvas, image, x, y);
+    };
+    match mode {
+        //the root window covers all the monitors, so filling it spans them
+        Mode::Fill | Mode::Span => {
+            let filled = image.resize_to_fill(width, height, FilterType::Triangle);
+            imageops::overlay(&mut canvas, &filled.to_rgba8(), 0, 0);
+        }
+        Mode::Fit => centered(
+            &mut canvas,
+            &image.resize(width, height, FilterType::Triangle).to_rgba8(),
+        ),
+        Mode::Center => centered(&mut canvas, &image.to_rgba8()),
+        Mode::Stretch => {
+            let stretched = image.resize_exact(width, height, FilterType::Triangle);
+            imageops::overlay(&mut canvas, &stretched.to_rgba8(), 0, 0);
+        }
+        Mode::Tile => {
+            let tile = image.to_rgba8();
+            for y in (0..height).step_by(tile.height().max(1) as usize) {
+                for x in (0..width).step_by(tile.width().max(1) as usize) {
+                    imageops::overlay(&mut canvas, &tile, x.into(), y.into());
+                }
+            }
+        }
+    }
+    canvas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+
+    //a white 40x20 image, with its top left pixel red so tiles can be told apart
+    fn image() -> DynamicImage {
+        let mut image = RgbaImage::from_pixel(40, 20, WHITE);
+        image.put_pixel(0, 0, RED);
+        DynamicImage::ImageRgba8(image)
+    }
+
+    fn covered(canvas: &RgbaImage, x: u32, y: u32) -> bool {
+        canvas.get_pixel(x, y).0[..3]
+            .iter()
+            .any(|channel| *channel > 0)
+    }
+
+    fn fully_covered(canvas: &RgbaImage) -> bool {
+        canvas
+            .enumerate_pixels()
+            .all(|(x, y, _)| covered(canvas, x, y))
+    }
+
+    #[test]
+    fn canvas_is_the_size_of_the_screen() {
+        for mode in Vec::from([
+            Mode::Fill,
+            Mode::Fit,
+            Mode::Center,
+            Mode::Tile,
+            Mode::Stretch,
+            Mode::Span,
+        ]) {
+            let canvas = fit(image(), 100, 100, mode);
+            assert_eq!(canvas.dimensions(), (100, 100), "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn fill_stretch_and_span_cover_the_screen() {
+        for mode in Vec::from([Mode::Fill, Mode::Stretch, Mode::Span]) {
+            assert!(fully_covered(&fit(image(), 100, 100, mode)), "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn fit_has_borders_on_the_short_side() {
+        //scaled to 100x50, so there are 25 rows of black above and below
+        let canvas = fit(image(), 100, 100, Mode::Fit);
+        assert!(!covered(&canvas, 50, 24));
+        assert!(covered(&canvas, 50, 25));
+        assert!(covered(&canvas, 50, 74));
+        assert!(!covered(&canvas, 50, 75));
+        assert!(covered(&canvas, 0, 50) && covered(&canvas, 99, 50));
+    }
+
+    #[test]
+    fn center_keeps_the_size_of_the_image() {
+        let canvas = fit(image(), 100, 100, Mode::Center);
+        assert_eq!(*canvas.get_pixel(30, 40), RED);
+        assert!(covered(&canvas, 69, 59));
+        assert!(!covered(&canvas, 29, 40));
+        assert!(!covered(&canvas, 30, 39));
+        assert!(!covered(&canvas, 70, 59));
+        assert!(!covered(&canvas, 69, 60));
+    }
+
+    #[test]
+    fn tile_repeats_from_the_top_left() {
+        let canvas = fit(image(), 100, 100, Mode::Tile);
+        assert!(fully_covered(&canvas));
+        for (x, y) in Vec::from([(0, 0), (40, 0), (80, 0), (0, 20), (80, 80)]) {
+            assert_eq!(*canvas.get_pixel(x, y), RED, "{}, {}", x, y);
+        }
+        assert_eq!(*canvas.get_pixel(1, 0), WHITE);
+    }
+
+    //needs an X server to draw on, like Xvfb:
+    //xvfb-run cargo test --features x11 -- --ignored
+    #[test]
+    #[ignore]
+    fn sets_the_root_pixmap_properties() {
+        let path = std::env::temp_dir().join(format!("dyn-wall-rs-{}.png", std::process::id()));
+        image().save(&path).unwrap();
+        let path = path.to_str().unwrap();
+
+        let (conn, screen_num) = x11rb::connect(None).unwrap();
+        let root = conn.setup().roots[screen_num].root;
+        let root_pixmap = |name: &[u8]| {
+            let atom = conn.intern_atom(false, name).unwrap().reply().unwrap().atom;
+            conn.get_property(false, root, atom, AtomEnum::PIXMAP, 0, 1)
+                .unwrap()
+                .reply()
+                .unwrap()
+                .value32()
+                .and_then(|mut value| value.next())
+        };
+
+        //set twice, so the pixmap from the first time is released by the second
+        let mut pixmaps = vec![];
+        for _ in 0..2 {
+            X11.set(path, &SetOptions::default()).unwrap();
+            let xroot = root_pixmap(ROOT_PIXMAP_ATOMS[0]);
+            let esetroot = root_pixmap(ROOT_PIXMAP_ATOMS[1]);
+            assert!(xroot.is_some_and(|pixmap| pixmap != 0));
+            assert_eq!(xroot, esetroot);
+            pixmaps.push(xroot);
+        }
+        assert_ne!(pixmaps[0], pixmaps[1]);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,217 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//running the programs that set the wallpaper (and the ones it's sent to), waiting for them to
+//finish so failures are noticed rather than reported as success
+use crate::errors::Errors;
+use std::{
+    io::Read,
+    process::{Child, Command, Stdio},
+    sync::{
+        mpsc::{channel, Sender},
+        RwLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+///How long a command can run for before it's stopped, when the command_timeout option isn't set
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+//how long a program that keeps running is given to start up before it's relied on
+const START_TIME: Duration = Duration::from_millis(500);
+//how long to wait for the output once a command has finished. Anything it started in the
+//background keeps the output open, so it can't be waited on until it closes
+const OUTPUT_WAIT: Duration = Duration::from_millis(100);
+
+//the command_timeout option, set once the config has been read
+static TIMEOUT: RwLock<Duration> = RwLock::new(DEFAULT_TIMEOUT);
+
+///Replaces how long commands can run for, in seconds. `None` goes back to `DEFAULT_TIMEOUT`
+pub fn set_timeout(secs: Option<u64>) {
+    *TIMEOUT.write().expect("Command timeout poisoned") =
+        secs.map_or(DEFAULT_TIMEOUT, Duration::from_secs);
+}
+
+///Splits a command into its words the way a POSIX shell would, so quoted arguments and paths
+///with spaces stay together
+pub fn split(command: &str) -> Result<Vec<String>, Errors> {
+    match shell_words::split(command) {
+        Ok(words) if !words.is_empty() => Ok(words),
+        _ => Err(Errors::CommandParseError(command.to_string())),
+    }
+}
+
+///Runs the command and waits for it to finish, stopping it if it takes too long. If it exits
+///with an error, what it printed to stderr is included. `name` is used to refer to the command
+///in errors
+pub fn run(command: &mut Command, name: &str) -> Result<(), Errors> {
+    wait(command, name, false).map(drop)
+}
+
+///Runs the command like `run`, returning what it printed to stdout
+pub fn output(command: &mut Command, name: &str) -> Result<String, Errors> {
+    wait(command.stdout(Stdio::piped()), name, true).map(Option::unwrap_or_default)
+}
+
+//reads the pipe to the end on another thread, since the command blocks if the pipe fills up
+fn read_pipe(mut pipe: Option<impl Read + Send + 'static>, text: Sender<String>) {
+    thread::spawn(move || {
+        let mut read = String::new();
+        if let Some(pipe) = pipe.as_mut() {
+            let _ = pipe.read_to_string(&mut read);
+        }
+        let _ = text.send(read);
+    });
+}
+
+//spawns the command and waits for it, returning its stdout if `read_stdout` is set
+fn wait(command: &mut Command, name: &str, read_stdout: bool) -> Result<Option<String>, Errors> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| Errors::ProgramRunError(name.to_string()))?;
+
+    let (output, stderr) = channel();
+    read_pipe(child.stderr.take(), output);
+    let stdout = read_stdout.then(|| {
+        let (output, stdout) = channel();
+        read_pipe(child.stdout.take(), output);
+        stdout
+    });
+
+    let timeout = *TIMEOUT.read().expect("Command timeout poisoned");
+    let start = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if start.elapsed() < timeout => thread::sleep(POLL_INTERVAL),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(Errors::CommandTimeoutError(
+                    name.to_string(),
+                    timeout.as_secs(),
+                ));
+            }
+            Err(_) => return Err(Errors::ProgramRunError(name.to_string())),
+        }
+    };
+
+    if status.success() {
+        return Ok(stdout.map(|stdout| stdout.recv_timeout(OUTPUT_WAIT).unwrap_or_default()));
+    }
+    let stderr = stderr.recv_timeout(OUTPUT_WAIT).unwrap_or_default();
+    Err(Errors::CommandFailedError(
+        name.to_string(),
+        status.code(),
+        stderr.trim().to_string(),
+    ))
+}
+
+///Starts a program that keeps running, checking that it hasn't stopped straight away
+pub fn start(command: &mut Command, name: &str) -> Result<Child, Errors> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .spawn()
+        .map_err(|_| Errors::ProgramRunError(name.to_string()))?;
+    thread::sleep(START_TIME);
+    match child.try_wait() {
+        Ok(None) => Ok(child),
+        Ok(Some(status)) => Err(Errors::CommandStartError(name.to_string(), status.code())),
+        Err(_) => Err(Errors::ProgramRunError(name.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hooks::{prepare, Change};
+
+    #[test]
+    fn split_keeps_quoted_arguments_together() {
+        assert_eq!(
+            split(r#"betterlockscreen -u "/home/me/My Pictures/1.png" --fx 'dim, blur'"#).unwrap(),
+            [
+                "betterlockscreen",
+                "-u",
+                "/home/me/My Pictures/1.png",
+                "--fx",
+                "dim, blur"
+            ]
+        );
+        assert_eq!(
+            split(r"feh --bg-fill /home/me/My\ Pictures/1.png").unwrap(),
+            ["feh", "--bg-fill", "/home/me/My Pictures/1.png"]
+        );
+    }
+
+    #[test]
+    fn split_rejects_unbalanced_quotes_and_empty_commands() {
+        assert!(matches!(
+            split(r#"notify-send "unfinished"#),
+            Err(Errors::CommandParseError(_))
+        ));
+        assert!(matches!(split("  "), Err(Errors::CommandParseError(_))));
+    }
+
+    #[test]
+    fn wall_with_spaces_stays_one_argument() {
+        let change = Change::new("/home/me/My Pictures/night 1.png");
+        let command = prepare(
+            r#"convert !WALL -blur 0x8 "/tmp/lock screen.png" --image=!WALL"#,
+            &change,
+        )
+        .unwrap();
+        assert_eq!(command.get_program(), "convert");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            args,
+            [
+                "/home/me/My Pictures/night 1.png",
+                "-blur",
+                "0x8",
+                "/tmp/lock screen.png",
+                "--image=/home/me/My Pictures/night 1.png",
+            ]
+        );
+    }
+
+    #[test]
+    fn output_returns_stdout_and_reports_stderr() {
+        let printed = output(
+            Command::new("sh").args(["-c", "echo 'file:///1.png'"]),
+            "sh",
+        );
+        assert_eq!(printed.unwrap(), "file:///1.png\n");
+        match output(
+            Command::new("sh").args(["-c", "echo ignored; echo 'No such key' >&2; exit 1"]),
+            "sh",
+        ) {
+            Err(Errors::CommandFailedError(name, code, stderr)) => {
+                assert_eq!(
+                    (name.as_str(), code, stderr.as_str()),
+                    ("sh", Some(1), "No such key")
+                )
+            }
+            other => panic!("expected the command to fail, got {:?}", other),
+        }
+    }
+}
@@ -18,15 +18,27 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use crate::{check_dir_exists, sun_timings, ConfigFileErrors, Errors, Time};
-use clap::Parser;
+use crate::{
+    backend::Mode,
+    check_dir_exists, command,
+    control::ControlCommand,
+    hooks::{self, Hook, Trigger},
+    images,
+    slideshow::{self, is_slideshow},
+    solar::Twilight,
+    sun_schedule, ConfigFileErrors, Errors, Time,
+};
+use chrono::{Local, NaiveDate};
+use clap::{CommandFactory, Parser, Subcommand};
 use dirs_next::config_dir;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::{
+    collections::BTreeMap,
     error::Error,
     fs::create_dir_all,
     fs::File,
     io::{Read, Write},
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
@@ -49,12 +61,13 @@ pub struct Args {
         value_name = "COMMAND",
         help = r#"Sends image as argument to command specified. Use alongside the directory option. If the command itself contains arguments, wrap in quotation ex. dyn-wall-rs -a /path/to/dir -l "betterlockscreen -u""#
     )]
+    #[serde(default, deserialize_with = "deserialize_programs")]
     pub programs: Option<Vec<String>>,
 
     #[arg(
         short,
         long,
-        help = "Prints change schedule. Use alongside directory option",
+        help = "Prints change schedule for the directory set in the config file",
         //requires = "directory",
         num_args = 0,
     )]
@@ -67,6 +80,7 @@ pub struct Args {
         value_name = "BACKEND",
         help = "Uses the specified method as the backend to change the wallpaper. Custom command can be used"
     )]
+    #[serde(default, deserialize_with = "deserialize_backend")]
     pub backend: Option<String>,
 
     #[arg(
@@ -96,6 +110,41 @@ pub struct Args {
     )]
     pub elevation: Option<f64>,
 
+    #[arg(
+        long,
+        help = "Picks the image based on how high the sun is rather than the time of day. Each image is matched with an elevation from the elevations config option, or spread between the lowest and highest points of the sun if that isn't set. Requires the lat and long options",
+        num_args = 0
+    )]
+    #[serde(default)]
+    pub solar: bool,
+
+    #[arg(skip)]
+    pub elevations: Option<Vec<f64>>,
+
+    //extensions of the files treated as images, only set through the config file
+    #[arg(skip)]
+    pub extensions: Option<Vec<String>>,
+
+    //modes for certain images or directories, only set through the config file
+    #[arg(skip)]
+    pub modes: Option<BTreeMap<String, Mode>>,
+
+    //whether gnome's dark style shows the image from the night directory, only set through the
+    //config file
+    #[arg(skip)]
+    pub dark_night: Option<bool>,
+
+    //commands run on certain changes, only set through the config file
+    #[arg(skip)]
+    pub hooks: Option<Vec<Hook>>,
+
+    #[arg(
+        long,
+        value_name = "TWILIGHT",
+        help = "How far below the horizon the sun goes during dawn and dusk, which sets how long the dawn and dusk directories are used for when syncing to the sun. Defaults to civil"
+    )]
+    pub twilight: Option<Twilight>,
+
     #[arg(
         short = 'i',
         long,
@@ -104,6 +153,57 @@ pub struct Args {
     )]
     pub days: Option<u32>,
 
+    #[arg(
+        long,
+        value_name = "COUNT",
+        help = "How many times in a row setting the wallpaper can fail before giving up. Failures to run a program are retried with a growing delay in between. Defaults to 5"
+    )]
+    pub max_failures: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "How long the programs that set the wallpaper (and the ones it's sent to) can run for before they're stopped. Defaults to 30"
+    )]
+    pub command_timeout: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "MONITOR",
+        help = "Name of the monitor to set the wallpaper on, for backends that can set one monitor (like sway). Several can be given, separated by commas. Also passed on to the programs in place of !MONITOR"
+    )]
+    pub monitor: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "TYPE",
+        help = "Transition to use when the wallpaper changes, for backends that have them (like swww). ex. fade, wipe, grow"
+    )]
+    pub transition: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "How long the transition takes, for backends that have them"
+    )]
+    pub transition_duration: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "MODE",
+        help = "How images that aren't the same size as the screen are fitted to it (fill, fit, center, tile, stretch or span)"
+    )]
+    pub mode: Option<Mode>,
+
+    #[arg(
+        long,
+        value_name = "DATE",
+        help = "Date to print the schedule for, in the format YYYY-MM-DD. Only changes the schedule when syncing to the sun. Use alongside the schedule option, with the directory and location set in the config file",
+        requires = "schedule"
+    )]
+    #[serde(skip)]
+    pub date: Option<NaiveDate>,
+
     #[arg(
         short = 'c',
         long = "current",
@@ -113,9 +213,83 @@ pub struct Args {
     #[serde(skip)]
     pub ret_curr_wp: bool,
 
+    #[command(subcommand)]
+    #[serde(skip)]
+    pub command: Option<Subcommands>,
+
     #[arg(skip)]
     #[serde(skip)]
     pub times: Option<Vec<Time>>,
+
+    //images matched up with the times, when they aren't simply every image in the directory
+    #[arg(skip)]
+    #[serde(skip)]
+    pub files: Option<Vec<String>>,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum Subcommands {
+    ///Converts an Apple dynamic wallpaper (.heic) into a directory of images and a matching config. Requires heif-convert from libheif
+    Import {
+        #[arg(value_name = "FILE", help = "The .heic file to import")]
+        file: String,
+
+        #[arg(
+            short,
+            long,
+            value_name = "DIRECTORY",
+            help = "Directory to put the images in. Defaults to the name of the file without the extension"
+        )]
+        output: Option<String>,
+    },
+
+    ///Writes the schedule for the directory out as a GNOME background slideshow, so GNOME can change the wallpaper itself with a crossfade. When syncing to the sun, the timings for the current day are used
+    Export {
+        #[arg(value_name = "FILE", help = "Where to write the slideshow (.xml)")]
+        output: String,
+
+        #[arg(
+            short,
+            long,
+            value_name = "SECONDS",
+            default_value_t = 5.0,
+            help = "How long the crossfade into each image lasts"
+        )]
+        transition: f64,
+    },
+
+    ///Tells the running instance to show the next image, until the next scheduled change
+    Next,
+
+    ///Tells the running instance to show the previous image, until the next scheduled change
+    Prev,
+
+    ///Stops the running instance from changing the wallpaper
+    Pause,
+
+    ///Lets the running instance change the wallpaper again, going back to the scheduled image
+    Resume,
+
+    ///Tells the running instance to read the wallpaper directory again
+    Reload,
+
+    ///Prints the wallpaper set by the running instance and when it next changes
+    Status,
+}
+
+impl Subcommands {
+    ///The command to send to the running instance, for the subcommands that control it
+    pub fn control(&self) -> Option<ControlCommand> {
+        match self {
+            Subcommands::Next => Some(ControlCommand::Next),
+            Subcommands::Prev => Some(ControlCommand::Prev),
+            Subcommands::Pause => Some(ControlCommand::Pause),
+            Subcommands::Resume => Some(ControlCommand::Resume),
+            Subcommands::Reload => Some(ControlCommand::Reload),
+            Subcommands::Status => Some(ControlCommand::Status),
+            Subcommands::Import { .. } | Subcommands::Export { .. } => None,
+        }
+    }
 }
 
 //not optimal, but it seems serde can really only work on structs. Would be great if I could
@@ -162,76 +336,291 @@ impl Args {
             } else {
                 config_args.elevation
             },
+            solar: cli_args.solar || config_args.solar,
+            elevations: config_args.elevations,
+            extensions: config_args.extensions,
+            modes: config_args.modes,
+            dark_night: config_args.dark_night,
+            hooks: config_args.hooks,
+            twilight: if cli_args.twilight.is_some() {
+                cli_args.twilight
+            } else {
+                config_args.twilight
+            },
             days: if cli_args.days.is_some() {
                 cli_args.days
             } else {
                 config_args.days
             },
+            max_failures: if cli_args.max_failures.is_some() {
+                cli_args.max_failures
+            } else {
+                config_args.max_failures
+            },
+            command_timeout: if cli_args.command_timeout.is_some() {
+                cli_args.command_timeout
+            } else {
+                config_args.command_timeout
+            },
+            monitor: if cli_args.monitor.is_some() {
+                cli_args.monitor
+            } else {
+                config_args.monitor
+            },
+            transition: if cli_args.transition.is_some() {
+                cli_args.transition
+            } else {
+                config_args.transition
+            },
+            transition_duration: if cli_args.transition_duration.is_some() {
+                cli_args.transition_duration
+            } else {
+                config_args.transition_duration
+            },
+            mode: if cli_args.mode.is_some() {
+                cli_args.mode
+            } else {
+                config_args.mode
+            },
+            date: cli_args.date,
             ret_curr_wp: cli_args.ret_curr_wp,
+            command: cli_args.command,
             times: temp_times,
+            files: None,
         };
-        //the default is all fields none, this is fine becuase if other options are used by
-        //themselves, specific errors come up.
-        if Args::default() == args {
-            Err("Directory not specified".into())
+        //commands with unbalanced quotes are caught now rather than when the wallpaper changes
+        let hook_commands = args.hooks.iter().flatten().map(|hook| &hook.command);
+        for prog in args
+            .programs
+            .iter()
+            .flatten()
+            .chain(args.backend.as_ref())
+            .chain(hook_commands)
+        {
+            command::split(prog)?;
         }
-        //if latitude is specified, then longitude and elevation is required as well, so we
-        //just need to check for one of them
-        else if let Some(lat) = args.lat {
-            if args.long.is_none() {
-                Err("Error: lat needs to be specified with long".into())
-            } else {
-                let dir = args.directory.to_owned();
-                match dir {
-                    None => Err("Error: Directory needs to be specified".into()),
-                    Some(dir) => {
-                        let dir = dir.as_str();
-                        match sun_timings(
-                            dir,
-                            lat,
-                            args.long.unwrap(),
-                            args.elevation.or_else(|| Some(0.0)).unwrap(),
-                        ) {
-                            Err(e) => Err(format!("Error: {}", e).into()),
-                            Ok(s) => {
-                                args.times = Some(s);
-                                Ok(args)
+        if let Some(hook) = args
+            .hooks
+            .iter()
+            .flatten()
+            .find(|hook| hook.on == Trigger::File && hook.file.is_none())
+        {
+            return Err(format!(
+                "Error: The hook {} is run on a file, so the file option needs to be set",
+                hook.command
+            )
+            .into());
+        }
+        //the directory is looked through with the new extensions, without switching to them
+        let extensions = args.extensions.clone();
+        images::with_extensions(extensions, || {
+            //the default is all fields none, this is fine becuase if other options are used by
+            //themselves, specific errors come up.
+            if Args::default() == args {
+                Err("Directory not specified".into())
+            }
+            //slideshows come with their own timings
+            else if args.directory.as_deref().is_some_and(is_slideshow)
+                && (args.lat.is_some() || args.long.is_some() || args.days.is_some())
+            {
+                Err("Error: A GNOME slideshow has its own timings, so it can't be used with the lat, long or days options".into())
+            }
+            //if latitude is specified, then longitude and elevation is required as well, so we
+            //just need to check for one of them
+            else if args.lat.is_some() {
+                if args.long.is_none() {
+                    Err("Error: lat needs to be specified with long".into())
+                } else {
+                    let dir = args.directory.to_owned();
+                    match dir {
+                        None => Err("Error: Directory needs to be specified".into()),
+                        Some(dir) => {
+                            let date = args.date.unwrap_or_else(|| Local::now().date_naive());
+                            match sun_schedule(dir.as_str(), &args, date) {
+                                Err(e) => Err(format!("Error: {}", e).into()),
+                                Ok(schedule) => {
+                                    if let Some((times, files)) = schedule {
+                                        args.times = Some(times);
+                                        args.files = Some(files);
+                                    }
+                                    Ok(args)
+                                }
                             }
                         }
                     }
                 }
+            } else if args.long.is_some() {
+                Err("Error: long neds to be specified with lat".into())
+            } else if args.solar {
+                Err("Error: The solar option requires lat and long to be specified".into())
+            }
+            //handle custom programs specified by user
+            else if args.programs.is_some() && args.directory.is_none() && !args.schedule {
+                Err("Error: The program option is to be used with a specified directory".into())
             }
-        } else if args.long.is_some() {
-            Err("Error: long neds to be specified with lat".into())
+            //handle custom backend specified by user
+            else if args.backend.is_some() && args.directory.is_none() {
+                Err("Error: The backend option is to be used with a specified directory".into())
+            } else if args.schedule && args.directory.is_none() {
+                Err(
+                    "Error: The schedule option is to be used alongside a specified directory"
+                        .into(),
+                )
+            } else {
+                if !args.ret_curr_wp {
+                    let dir = args.directory.to_owned().unwrap();
+                    check_dir_exists(&dir)?;
+                    if is_slideshow(&dir) {
+                        let (times, files) = slideshow::load(&dir)?;
+                        args.times = Some(times);
+                        args.files = Some(files);
+                    }
+                }
+                Ok(args)
+            }
+        })
+    }
+
+    ///Switches to the settings that are kept for the whole program rather than passed around:
+    ///the image extensions, the command timeout and the hooks. Done once the options are known to
+    ///work, so a config that's rejected doesn't change anything
+    pub fn apply(&self) {
+        images::set_extensions(self.extensions.clone());
+        command::set_timeout(self.command_timeout);
+        hooks::set_hooks(self.hooks.clone());
+    }
+}
+
+///Makes the directory option in command line arguments that were given in `cwd` absolute, so
+///the arguments mean the same thing wherever they are parsed again
+pub fn absolute_directory(cwd: &Path, cli: Vec<String>) -> Vec<String> {
+    let command = Args::command();
+    let takes_value =
+        |found: Option<&clap::Arg>| found.is_some_and(|arg| arg.get_action().takes_values());
+    let short_takes_value = |short: char| {
+        takes_value(
+            command
+                .get_arguments()
+                .find(|arg| arg.get_short() == Some(short)),
+        )
+    };
+    let long_takes_value = |long: &str| {
+        takes_value(
+            command
+                .get_arguments()
+                .find(|arg| arg.get_long() == Some(long)),
+        )
+    };
+    let absolute = |dir: &str| cwd.join(dir).to_string_lossy().to_string();
+
+    let mut resolved = Vec::with_capacity(cli.len());
+    let mut cli = cli.into_iter();
+    while let Some(arg) = cli.next() {
+        //anything after this is a subcommand and its arguments
+        if arg == "--" || !arg.starts_with('-') {
+            resolved.push(arg);
+            break;
         }
-        //handle custom programs specified by user
-        else if args.programs.is_some() && args.directory.is_none() && !args.schedule {
-            Err("Error: The program option is to be used with a specified directory".into())
+        if let Some(long) = arg.strip_prefix("--") {
+            match long.split_once('=') {
+                Some(("directory", dir)) => resolved.push(format!("--directory={}", absolute(dir))),
+                Some(_) => resolved.push(arg),
+                None if long == "directory" => {
+                    resolved.push(arg);
+                    resolved.extend(cli.next().map(|dir| absolute(&dir)));
+                }
+                None if long_takes_value(long) => {
+                    resolved.push(arg);
+                    resolved.extend(cli.next());
+                }
+                None => resolved.push(arg),
+            }
+            continue;
         }
-        //handle custom backend specified by user
-        else if args.backend.is_some() && args.directory.is_none() {
-            Err("Error: The backend option is to be used with a specified directory".into())
-        } else if args.schedule && args.directory.is_none() {
-            Err("Error: The schedule option is to be used alongside a specified directory".into())
-        } else {
-            if !args.ret_curr_wp {
-                check_dir_exists(&args.directory.to_owned().unwrap())?;
+        //short options can be bunched together, and the first one that takes a value takes the
+        //rest of the argument, or the next argument if there's nothing left
+        let shorts = &arg[1..];
+        match shorts
+            .char_indices()
+            .find(|(_, short)| short_takes_value(*short))
+        {
+            Some((at, short)) if at + short.len_utf8() == shorts.len() => {
+                let value = cli.next();
+                resolved.push(arg);
+                if short == 'd' {
+                    resolved.extend(value.map(|dir| absolute(&dir)));
+                } else {
+                    resolved.extend(value);
+                }
+            }
+            Some((at, 'd')) => {
+                let (flags, dir) = shorts.split_at(at + 1);
+                let (equals, dir) = match dir.strip_prefix('=') {
+                    Some(dir) => ("=", dir),
+                    None => ("", dir),
+                };
+                resolved.push(format!("-{}{}{}", flags, equals, absolute(dir)));
             }
-            Ok(args)
+            _ => resolved.push(arg),
         }
     }
+    resolved.extend(cli);
+    resolved
+}
+
+//a command in the config file can be a string, split like a shell would, or a list of arguments.
+//Lists are joined back into a quoted string so both are handled the same way from then on
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CommandForm {
+    Line(String),
+    Words(Vec<String>),
+}
+
+impl From<CommandForm> for String {
+    fn from(form: CommandForm) -> Self {
+        match form {
+            CommandForm::Line(line) => line,
+            CommandForm::Words(words) => shell_words::join(words),
+        }
+    }
+}
+
+fn deserialize_programs<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let forms: Option<Vec<CommandForm>> = Option::deserialize(deserializer)?;
+    Ok(forms.map(|forms| forms.into_iter().map(String::from).collect()))
+}
+
+fn deserialize_backend<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let form: Option<CommandForm> = Option::deserialize(deserializer)?;
+    Ok(form.map(String::from))
+}
+
+pub(crate) fn deserialize_command<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    CommandForm::deserialize(deserializer).map(String::from)
+}
+
+///Path of the config file
+pub fn config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("dyn-wall-rs").join("config.toml"))
 }
 
 //parse config file
 type UserInput = (Option<Vec<Time>>, Args);
+#[allow(clippy::unnecessary_lazy_evaluations)]
 pub fn config_parse(cli_args_used: bool) -> Result<UserInput, Box<dyn Error>> {
-    let file = File::open(format!(
-        "{}/dyn-wall-rs/config.toml",
-        config_dir()
-            .ok_or_else(|| Errors::ConfigFileError(ConfigFileErrors::NotFound))?
-            .to_str()
-            .unwrap()
-    ))
+    let file = File::open(
+        config_path().ok_or_else(|| Errors::ConfigFileError(ConfigFileErrors::NotFound))?,
+    )
     .map_err(|_| Errors::ConfigFileError(ConfigFileErrors::NotFound));
 
     let file = match file {
@@ -289,6 +678,7 @@ pub fn config_parse(cli_args_used: bool) -> Result<UserInput, Box<dyn Error>> {
     }
 }
 
+#[allow(clippy::unnecessary_lazy_evaluations)]
 fn create_config() -> Result<(), Box<dyn Error>> {
     let config_dir =
         config_dir().ok_or_else(|| Errors::ConfigFileError(ConfigFileErrors::NotFound))?;
@@ -324,11 +714,145 @@ fn create_config() -> Result<(), Box<dyn Error>> {
 #times = []
 #directory = "/path/to/dir"
 #backend = "feh"
-#program = ["echo test1", "echo test2"]
+#programs = ["echo test1", "echo test2"]
+# Commands can also be written as a list of arguments, so nothing needs quoting
+#programs = [["betterlockscreen", "-u", "!WALL"]]
 #lat = 99
 #long = -99
-#elevation = 99"#;
+#elevation = 99
+#twilight = "civil"
+#solar = true
+#elevations = [-18, -6, 0, 10, 30, 10, 0, -6]
+#max_failures = 5
+#command_timeout = 30
+#monitor = "HDMI-A-1"
+#transition = "fade"
+#transition_duration = 2
+#mode = "fill"
+# With GNOME's dark style on, show the image with the same name in the night directory next to
+# the day one, instead of the same image
+#dark_night = true
+# Only files with these extensions are used as wallpapers (files without an extension are
+# checked to see if they're an image). Hidden files are always skipped
+#extensions = ["jpg", "jpeg", "png", "webp"]
+# The mode can be changed for certain images or directories, matched by the end of their path.
+# This table needs to go after the options above
+#[modes]
+#"night" = "fit"
+#"day/3.png" = "center"
+# Hooks are commands that only run on certain changes, unlike programs which run every time.
+# on can be change (the image is different), phase (the phase of the day is different), sunrise,
+# sunset, daily (the first change of the day) or file (the image is the one in file). The image
+# isn't added to the end of the command, so use the placeholders (like !WALL) for it.
+# Hooks need to go at the end of the config file
+#[[hooks]]
+#on = "sunset"
+#command = "gsettings set org.gnome.desktop.interface color-scheme prefer-dark"
+#[[hooks]]
+#on = "change"
+#command = ["betterlockscreen", "-u", "!WALL"]"#;
 
     config_file.write_all(contents.as_bytes())?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_lists_round_trip_through_split() {
+        let programs = vec![
+            vec!["betterlockscreen", "-u", "!WALL"],
+            vec!["notify-send", "New wallpaper", "it's \"!WALL\""],
+            vec!["/opt/my tools/lock", "--image=!WALL", ""],
+        ];
+        let backend = vec!["my setter", "--mode", "a b"];
+        let config = format!("programs = {:?}\nbackend = {:?}\n", programs, backend);
+
+        let args: Args = toml::from_str(&config).unwrap();
+        let split_programs: Vec<Vec<String>> = args
+            .programs
+            .unwrap()
+            .iter()
+            .map(|prog| command::split(prog).unwrap())
+            .collect();
+        assert_eq!(split_programs, programs);
+        assert_eq!(command::split(&args.backend.unwrap()).unwrap(), backend);
+    }
+
+    #[test]
+    fn command_strings_are_kept_as_written() {
+        let args: Args =
+            toml::from_str(r#"programs = ["betterlockscreen -u '/home/me/My Pictures'"]"#).unwrap();
+        assert_eq!(
+            args.programs.unwrap(),
+            ["betterlockscreen -u '/home/me/My Pictures'"]
+        );
+    }
+
+    fn absolute(cli: &[&str]) -> Vec<String> {
+        absolute_directory(
+            Path::new("/home/me"),
+            cli.iter().map(|arg| arg.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn directory_options_are_made_absolute() {
+        assert_eq!(absolute(&["-d", "walls"]), ["-d", "/home/me/walls"]);
+        assert_eq!(
+            absolute(&["--directory", "walls"]),
+            ["--directory", "/home/me/walls"]
+        );
+        assert_eq!(
+            absolute(&["--directory=walls"]),
+            ["--directory=/home/me/walls"]
+        );
+        assert_eq!(
+            absolute(&["-d", "/usr/share/walls"]),
+            ["-d", "/usr/share/walls"]
+        );
+        assert_eq!(absolute(&["-d", "../walls"]), ["-d", "/home/me/../walls"]);
+        //the option can come last without a value, for clap to complain about
+        assert_eq!(absolute(&["-s", "-d"]), ["-s", "-d"]);
+    }
+
+    #[test]
+    fn bundled_short_options_are_made_absolute() {
+        assert_eq!(absolute(&["-sd", "walls"]), ["-sd", "/home/me/walls"]);
+        assert_eq!(absolute(&["-dwalls"]), ["-d/home/me/walls"]);
+        assert_eq!(absolute(&["-sdwalls"]), ["-sd/home/me/walls"]);
+        assert_eq!(absolute(&["-d=walls"]), ["-d=/home/me/walls"]);
+        //the value of an earlier option isn't a directory, even if it has a d in it
+        assert_eq!(
+            absolute(&["-bfeh-d", "-d", "walls"]),
+            ["-bfeh-d", "-d", "/home/me/walls"]
+        );
+        assert_eq!(absolute(&["-sb", "-d", "-c"]), ["-sb", "-d", "-c"]);
+    }
+
+    #[test]
+    fn values_of_other_options_are_left_alone() {
+        assert_eq!(
+            absolute(&["-p", "-d", "--backend", "--directory", "-s"]),
+            ["-p", "-d", "--backend", "--directory", "-s"]
+        );
+        assert_eq!(
+            absolute(&["--programs=-d", "-i", "2", "-d", "walls"]),
+            ["--programs=-d", "-i", "2", "-d", "/home/me/walls"]
+        );
+    }
+
+    #[test]
+    fn arguments_after_a_subcommand_are_left_alone() {
+        assert_eq!(
+            absolute(&["-s", "--", "-d", "walls"]),
+            ["-s", "--", "-d", "walls"]
+        );
+        assert_eq!(
+            absolute(&["import", "-d", "walls", "--directory=walls"]),
+            ["import", "-d", "walls", "--directory=walls"]
+        );
+    }
+}
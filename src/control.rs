@@ -0,0 +1,350 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//controlling a running instance. The daemon listens on a Unix domain socket, reading one command
+//per connection and writing back a reply before closing it
+use chrono::NaiveDateTime;
+use std::{
+    error::Error,
+    fmt,
+    str::FromStr,
+    sync::{mpsc::Sender, RwLock},
+};
+
+#[cfg(unix)]
+use std::{
+    env,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::mpsc::channel,
+    thread,
+};
+
+///Commands accepted by a running instance
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ControlCommand {
+    ///Shows the image after the current one, until the next scheduled change
+    Next,
+    ///Shows the image before the current one, until the next scheduled change
+    Prev,
+    ///Stops scheduled changes
+    Pause,
+    ///Starts scheduled changes again, going back to the scheduled image
+    Resume,
+    ///Reads the config file and the wallpaper directory again and works out the schedule from
+    ///scratch, keeping the old one if that fails
+    Reload,
+    ///Replies with the current wallpaper and when it next changes
+    Status,
+    ///Starts over using the command line arguments passed on from a second instance, along with
+    ///the directory it was started in, so relative paths still work
+    Args { cwd: String, args: Vec<String> },
+}
+
+impl FromStr for ControlCommand {
+    type Err = String;
+
+    fn from_str(command: &str) -> Result<Self, Self::Err> {
+        let command = command.trim_end_matches(['\n', '\r']);
+        //the arguments are separated by nul characters, since they can contain anything else
+        //apart from new lines
+        if let Some(args) = command.strip_prefix("args\0") {
+            let mut args = args.split('\0').map(String::from);
+            return Ok(ControlCommand::Args {
+                cwd: args.next().unwrap_or_default(),
+                args: args.collect(),
+            });
+        }
+
+        match command.trim() {
+            "next" => Ok(ControlCommand::Next),
+            "prev" => Ok(ControlCommand::Prev),
+            "pause" => Ok(ControlCommand::Pause),
+            "resume" => Ok(ControlCommand::Resume),
+            "reload" => Ok(ControlCommand::Reload),
+            "status" => Ok(ControlCommand::Status),
+            other => Err(format!("Unknown command: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for ControlCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let command = match self {
+            ControlCommand::Next => "next",
+            ControlCommand::Prev => "prev",
+            ControlCommand::Pause => "pause",
+            ControlCommand::Resume => "resume",
+            ControlCommand::Reload => "reload",
+            ControlCommand::Status => "status",
+            ControlCommand::Args { cwd, args } => {
+                //each argument goes after a nul of its own, so no arguments and one empty one
+                //aren't mixed up
+                write!(f, "args\0{}", cwd)?;
+                return args.iter().try_for_each(|arg| write!(f, "\0{}", arg));
+            }
+        };
+        write!(f, "{}", command)
+    }
+}
+
+///A command sent to the daemon, along with where to send the reply
+pub struct Request {
+    pub command: ControlCommand,
+    pub reply: Sender<Result<String, String>>,
+}
+
+///What the daemon is currently showing
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Status {
+    pub wallpaper: String,
+    pub next_change: Option<NaiveDateTime>,
+    pub paused: bool,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Wallpaper: {}", self.wallpaper)?;
+        if let Some(next_change) = self.next_change {
+            writeln!(f, "Next change: {}", next_change.format("%Y-%m-%d %H:%M"))?;
+        }
+        if self.paused {
+            writeln!(f, "Paused")?;
+        }
+        Ok(())
+    }
+}
+
+//kept up to date by the daemon, so it can be read without waiting on it
+static STATUS: RwLock<Status> = RwLock::new(Status {
+    wallpaper: String::new(),
+    next_change: None,
+    paused: false,
+});
+
+///Records what the daemon is showing, for anything asking for its status
+pub fn publish(status: Status) {
+    let old = std::mem::replace(&mut *STATUS.write().expect("Status poisoned"), status);
+    //anything watching over D-Bus is told what changed
+    #[cfg(feature = "dbus")]
+    crate::dbus::status_changed(&old, &self::status());
+    #[cfg(not(feature = "dbus"))]
+    drop(old);
+}
+
+///Returns what the daemon last said it was showing
+pub fn status() -> Status {
+    STATUS.read().expect("Status poisoned").clone()
+}
+
+///Sends a command to the daemon without waiting for the reply. Returns false once the daemon
+///has stopped taking commands
+pub fn notify(requests: &Sender<Request>, command: ControlCommand) -> bool {
+    let (reply, _) = std::sync::mpsc::channel();
+    requests.send(Request { command, reply }).is_ok()
+}
+
+///Path of the control socket, in `$XDG_RUNTIME_DIR` if it's set
+#[cfg(unix)]
+pub fn socket_path() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("dyn-wall-rs.sock"),
+        //the temporary directory is shared between users, so each one gets their own socket
+        None => env::temp_dir().join(match env::var("USER") {
+            Ok(user) => format!("dyn-wall-rs-{}.sock", user),
+            Err(_) => String::from("dyn-wall-rs.sock"),
+        }),
+    }
+}
+
+///Starts listening for commands on the control socket in the background, sending each one down
+///`requests`. Fails if another instance is already listening
+#[cfg(unix)]
+pub fn serve(requests: Sender<Request>) -> Result<(), Box<dyn Error>> {
+    serve_at(socket_path(), requests)
+}
+
+#[cfg(unix)]
+fn serve_at(path: PathBuf, requests: Sender<Request>) -> Result<(), Box<dyn Error>> {
+    if UnixStream::connect(&path).is_ok() {
+        return Err(format!(
+            "Another instance is already listening on {}",
+            path.display()
+        )
+        .into());
+    }
+    //left behind by an instance that didn't shut down cleanly
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if handle_client(stream, &requests).is_err() {
+                //the daemon has stopped listening for commands
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+//only fails once the daemon stops taking requests, problems with a single client are ignored
+#[cfg(unix)]
+fn handle_client(stream: UnixStream, requests: &Sender<Request>) -> Result<(), ()> {
+    let mut line = String::new();
+    if BufReader::new(&stream).read_line(&mut line).is_err() {
+        return Ok(());
+    }
+
+    let reply = match line.parse() {
+        Ok(command) => {
+            let (reply, response) = channel();
+            requests.send(Request { command, reply }).map_err(|_| ())?;
+            match response.recv() {
+                Ok(Ok(reply)) => reply,
+                Ok(Err(e)) => format!("Error: {}", e),
+                Err(_) => String::new(),
+            }
+        }
+        Err(e) => e,
+    };
+    let _ = (&stream).write_all(reply.as_bytes());
+    Ok(())
+}
+
+///Sends a command to the running instance and returns its reply
+#[cfg(unix)]
+pub fn send(command: ControlCommand) -> Result<String, Box<dyn Error>> {
+    send_to(&socket_path(), command)
+}
+
+#[cfg(unix)]
+fn send_to(path: &Path, command: ControlCommand) -> Result<String, Box<dyn Error>> {
+    let mut stream = UnixStream::connect(path).map_err(|_| {
+        format!(
+            "Unable to connect to {}, make sure dyn-wall-rs is running",
+            path.display()
+        )
+    })?;
+    writeln!(stream, "{}", command)?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+
+    let mut reply = String::new();
+    std::io::Read::read_to_string(&mut stream, &mut reply)?;
+    Ok(reply)
+}
+
+//there's nothing to listen on, so the daemon just runs on its own
+#[cfg(not(unix))]
+pub fn serve(_requests: Sender<Request>) -> Result<(), Box<dyn Error>> {
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn send(_command: ControlCommand) -> Result<String, Box<dyn Error>> {
+    Err("The control socket is only available on Unix systems".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_round_trip() {
+        let commands = [
+            ControlCommand::Next,
+            ControlCommand::Prev,
+            ControlCommand::Pause,
+            ControlCommand::Resume,
+            ControlCommand::Reload,
+            ControlCommand::Status,
+            ControlCommand::Args {
+                cwd: String::from("/home/user/my walls"),
+                args: vec![
+                    String::from("-d"),
+                    String::from("day\tnight"),
+                    String::new(),
+                ],
+            },
+            ControlCommand::Args {
+                cwd: String::from("/"),
+                args: vec![],
+            },
+        ];
+        for command in Vec::from(commands) {
+            //sent as a line, so the new line is trimmed off when it's read
+            let line = format!("{}\n", command);
+            assert_eq!(line.parse::<ControlCommand>(), Ok(command));
+        }
+    }
+
+    #[test]
+    fn commands_are_parsed() {
+        assert_eq!("next\r\n".parse(), Ok(ControlCommand::Next));
+        assert_eq!(" status ".parse(), Ok(ControlCommand::Status));
+        assert_eq!(
+            "args\0/tmp\0-s\0--date\x002024-03-10".parse(),
+            Ok(ControlCommand::Args {
+                cwd: String::from("/tmp"),
+                args: vec![
+                    String::from("-s"),
+                    String::from("--date"),
+                    String::from("2024-03-10")
+                ],
+            })
+        );
+        assert!("Next".parse::<ControlCommand>().is_err());
+        assert!("args".parse::<ControlCommand>().is_err());
+        assert!("".parse::<ControlCommand>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commands_are_sent_over_the_socket() {
+        let path = env::temp_dir().join(format!("dyn-wall-rs-control-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let (requests, received) = channel();
+        serve_at(path.clone(), requests).unwrap();
+        //only one instance can listen at a time
+        let (others, _) = channel();
+        assert!(serve_at(path.clone(), others).is_err());
+
+        let daemon = thread::spawn(move || {
+            for reply in Vec::from([Ok("Paused"), Err("Nothing to go back to")]) {
+                let request: Request = received.recv().unwrap();
+                let reply = reply
+                    .map(|reply| format!("{} after {}", reply, request.command))
+                    .map_err(String::from);
+                request.reply.send(reply).unwrap();
+            }
+        });
+        assert_eq!(
+            send_to(&path, ControlCommand::Pause).unwrap(),
+            "Paused after pause"
+        );
+        assert_eq!(
+            send_to(&path, ControlCommand::Prev).unwrap(),
+            "Error: Nothing to go back to"
+        );
+        daemon.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(send_to(&path, ControlCommand::Status).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//the org.dynwall.Daemon service on the session bus, for desktop widgets and panel applets.
+//Commands are passed on to the daemon the same way as the ones from the control socket
+use crate::control::{self, ControlCommand, Request, Status};
+use std::{
+    error::Error,
+    sync::{
+        mpsc::{channel, Sender},
+        OnceLock,
+    },
+};
+use zbus::{
+    block_on,
+    blocking::{connection, object_server::InterfaceRef, Connection},
+    fdo, interface,
+};
+
+const NAME: &str = "org.dynwall.Daemon";
+const PATH: &str = "/org/dynwall/Daemon";
+
+//kept around so signals can be sent whenever the wallpaper changes
+static CONNECTION: OnceLock<Connection> = OnceLock::new();
+
+struct Daemon {
+    requests: Sender<Request>,
+}
+
+impl Daemon {
+    fn send(&self, command: ControlCommand) -> fdo::Result<()> {
+        let (reply, response) = channel();
+        self.requests
+            .send(Request { command, reply })
+            .map_err(|_| fdo::Error::Failed(String::from("The daemon has stopped")))?;
+        match response.recv() {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(e)) => Err(fdo::Error::Failed(e)),
+            Err(_) => Err(fdo::Error::Failed(String::from("The daemon has stopped"))),
+        }
+    }
+}
+
+#[interface(name = "org.dynwall.Daemon")]
+impl Daemon {
+    ///Shows the next image, until the next scheduled change
+    fn next(&self) -> fdo::Result<()> {
+        self.send(ControlCommand::Next)
+    }
+
+    ///Stops scheduled changes
+    fn pause(&self) -> fdo::Result<()> {
+        self.send(ControlCommand::Pause)
+    }
+
+    ///Starts scheduled changes again
+    fn resume(&self) -> fdo::Result<()> {
+        self.send(ControlCommand::Resume)
+    }
+
+    ///Path of the image currently set as the wallpaper
+    #[zbus(property)]
+    fn current_wallpaper(&self) -> String {
+        control::status().wallpaper
+    }
+
+    ///When the wallpaper next changes, as local time in the format YYYY-MM-DDTHH:MM:SS. Empty
+    ///if it isn't known
+    #[zbus(property)]
+    fn next_change(&self) -> String {
+        control::status()
+            .next_change
+            .map(|next_change| next_change.format("%Y-%m-%dT%H:%M:%S").to_string())
+            .unwrap_or_default()
+    }
+
+    ///Sent with the path of the image whenever the wallpaper is set
+    #[zbus(signal)]
+    async fn wallpaper_changed(
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+        path: &str,
+    ) -> zbus::Result<()>;
+}
+
+///Connects to the session bus and takes the org.dynwall.Daemon name, passing commands on down
+///`requests`. The bus address is taken from `DBUS_SESSION_BUS_ADDRESS`, so a private bus can be
+///used by setting it
+pub fn serve(requests: Sender<Request>) -> Result<(), Box<dyn Error>> {
+    let connection = connection::Builder::session()?
+        .name(NAME)?
+        .serve_at(PATH, Daemon { requests })?
+        .build()?;
+    let _ = CONNECTION.set(connection);
+    Ok(())
+}
+
+//runs `emit` with the interface being served, if the service is running
+fn with_interface(emit: impl FnOnce(&InterfaceRef<Daemon>) -> zbus::Result<()>) {
+    if let Some(connection) = CONNECTION.get() {
+        if let Err(e) = connection
+            .object_server()
+            .interface::<_, Daemon>(PATH)
+            .and_then(|iface| emit(&iface))
+        {
+            eprintln!("Unable to send a D-Bus signal: {}", e);
+        }
+    }
+}
+
+///Sends PropertiesChanged for the properties that differ between `old` and `status`. Called
+///after `status` has been published, since the values are read back from it
+pub fn status_changed(old: &Status, status: &Status) {
+    with_interface(|iface| {
+        let daemon = iface.get();
+        if old.wallpaper != status.wallpaper {
+            block_on(daemon.current_wallpaper_changed(iface.signal_emitter()))?;
+        }
+        if old.next_change != status.next_change {
+            block_on(daemon.next_change_changed(iface.signal_emitter()))?;
+        }
+        Ok(())
+    });
+}
+
+///Sends the WallpaperChanged signal, if the service is running
+pub fn wallpaper_changed(filepath: &str) {
+    //updated straight away so anything reacting to the signal sees the new wallpaper
+    control::publish(Status {
+        wallpaper: filepath.to_string(),
+        ..control::status()
+    });
+
+    with_interface(|iface| block_on(Daemon::wallpaper_changed(iface.signal_emitter(), filepath)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        env,
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+        sync::mpsc::Receiver,
+        thread,
+        time::Duration,
+    };
+    use zbus::blocking::{fdo::PropertiesProxy, Proxy};
+
+    //a session bus of its own, stopped when dropped
+    struct PrivateBus(Child);
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    fn private_bus() -> Option<PrivateBus> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .ok()?;
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+        env::set_var("DBUS_SESSION_BUS_ADDRESS", address.trim());
+        Some(PrivateBus(daemon))
+    }
+
+    //takes the next item from `items` on another thread, so a missing one fails instead of hanging
+    fn next_within<T: Send + 'static>(
+        mut items: impl Iterator<Item = T> + Send + 'static,
+    ) -> Receiver<T> {
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            if let Some(item) = items.next() {
+                let _ = sender.send(item);
+            }
+        });
+        receiver
+    }
+
+    #[test]
+    fn serves_commands_signals_and_properties() {
+        let _bus = match private_bus() {
+            Some(bus) => bus,
+            None => {
+                eprintln!("dbus-daemon isn't installed, skipping");
+                return;
+            }
+        };
+
+        let (requests, commands) = channel::<Request>();
+        serve(requests).unwrap();
+        thread::spawn(move || {
+            for request in commands {
+                let _ = request.reply.send(Ok(request.command.to_string()));
+            }
+        });
+
+        let client = Connection::session().unwrap();
+        let proxy = Proxy::new(&client, NAME, PATH, NAME).unwrap();
+        let properties = PropertiesProxy::builder(&client)
+            .destination(NAME)
+            .unwrap()
+            .path(PATH)
+            .unwrap()
+            .build()
+            .unwrap();
+        let signal = next_within(proxy.receive_signal("WallpaperChanged").unwrap());
+        let changed = next_within(properties.receive_properties_changed().unwrap());
+
+        proxy.call_method("Next", &()).unwrap();
+
+        wallpaper_changed("/tmp/with space/1.png");
+        let signal = signal.recv_timeout(Duration::from_secs(5)).unwrap();
+        let path: String = signal.body().deserialize().unwrap();
+        assert_eq!(path, "/tmp/with space/1.png");
+
+        let changed = changed.recv_timeout(Duration::from_secs(5)).unwrap();
+        let args = changed.args().unwrap();
+        assert_eq!(args.interface_name().as_str(), NAME);
+        assert!(args.changed_properties().contains_key("CurrentWallpaper"));
+
+        let current: String = proxy.get_property("CurrentWallpaper").unwrap();
+        assert_eq!(current, "/tmp/with space/1.png");
+    }
+}
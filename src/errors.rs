@@ -34,7 +34,17 @@ pub enum Errors {
     DirNonExistantError(String),
     NoFilesFoundError(String),
     ConfigFileError(ConfigFileErrors),
+    #[deprecated(note = "an unknown backend is run as a custom command, so this isn't returned")]
     BackendNotFoundError(String),
+    SunPositionError(String),
+    ImportError(String),
+    AlreadyRunningError(Option<String>),
+    CommandFailedError(String, Option<i32>, String),
+    CommandTimeoutError(String, u64),
+    CommandStartError(String, Option<i32>),
+    CommandParseError(String),
+    IpcError(String),
+    X11Error(String),
 }
 
 #[derive(Debug)]
@@ -42,6 +52,7 @@ pub enum Errors {
 pub enum ConfigFileErrors {
     Empty,
     FileTimeMismatch,
+    FileElevationMismatch,
     FormattingError,
     NotFound,
     OutOfOrder,
@@ -74,6 +85,7 @@ impl fmt::Display for Errors {
                 match cause {
                     ConfigFileErrors::Empty => write!(f, "{}: config file is empty", template),
                     ConfigFileErrors::FileTimeMismatch => write!(f, "{}: the number of times listed in the config file does not equal the number of files in directory", template),
+                    ConfigFileErrors::FileElevationMismatch => write!(f, "{}: the number of elevations listed in the config file does not equal the number of files in directory", template),
                     ConfigFileErrors::FormattingError => write!(f, "{}: config file not formatted correctly", template),
                     ConfigFileErrors::NotFound => write!(f, "{}: config file not found. One has been created at {}{}dyn-wall-rs{}config.toml for you to edit", template, config_dir().expect("No config directory found").to_str().unwrap(), DIR_SLASH, DIR_SLASH),
                     ConfigFileErrors::OutOfOrder => write!(f, "{}: the order of the times are incorrect", template),
@@ -82,7 +94,38 @@ impl fmt::Display for Errors {
                     ConfigFileErrors::Other(other_err) => write!(f, "{}: {}", template, other_err),
                 }
             }
+            #[allow(deprecated)]
             Errors::BackendNotFoundError(backend) => write!(f, "Backend '{}' not found", backend),
+            Errors::SunPositionError(cause) => {
+                write!(f, "Unable to work out the position of the sun: {}", cause)
+            }
+            Errors::ImportError(cause) => write!(f, "Unable to import wallpaper: {}", cause),
+            Errors::AlreadyRunningError(pid) => match pid {
+                Some(pid) => write!(f, "dyn-wall-rs is already running (pid {})", pid),
+                None => write!(f, "dyn-wall-rs is already running"),
+            },
+            Errors::CommandFailedError(prog, code, stderr) => {
+                match code {
+                    Some(code) => write!(f, "{} exited with code {}", prog, code)?,
+                    None => write!(f, "{} was stopped by a signal", prog)?,
+                }
+                if !stderr.is_empty() {
+                    write!(f, ": {}", stderr)?;
+                }
+                Ok(())
+            }
+            Errors::CommandTimeoutError(prog, secs) => {
+                write!(f, "{} didn't finish within {} seconds", prog, secs)
+            }
+            Errors::CommandStartError(prog, code) => match code {
+                Some(code) => write!(f, "{} exited with code {} straight after starting", prog, code),
+                None => write!(f, "{} was stopped by a signal straight after starting", prog),
+            },
+            Errors::CommandParseError(prog) => {
+                write!(f, "Unable to split '{}' into arguments, check its quotes", prog)
+            }
+            Errors::IpcError(message) => write!(f, "Error from sway: {}", message),
+            Errors::X11Error(message) => write!(f, "Unable to set the X11 background: {}", message),
         }
     }
 }
@@ -0,0 +1,627 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//importing of Apple dynamic wallpapers. These are HEIF files holding every frame of the
+//wallpaper as a separate image, with the schedule stored as a base64 encoded binary plist in the
+//XMP metadata, under apple_desktop:solar (position of the sun) or apple_desktop:h24 (time of day)
+use crate::{command, errors::Errors, time_track::Time};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use plist::Value;
+use serde::Serialize;
+use std::{
+    error::Error,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+///How the frames of the wallpaper are scheduled
+#[derive(Debug, PartialEq)]
+pub enum HeicSchedule {
+    ///Frame index for each fraction of the day, sorted by time
+    Times(Vec<(f64, usize)>),
+    ///Frame index for each elevation of the sun, in the order they are shown through the day
+    Solar(Vec<(f64, usize)>),
+}
+
+///Extracts the frames of the dynamic wallpaper at `file` into the `output` directory, named in
+///the order they are shown, and writes a config file matching them next to the directory, at
+///`output.toml`, so it isn't picked up as one of the images.
+///Frames are extracted using heif-convert, which comes with libheif
+pub fn import(file: &str, output: &str) -> Result<(), Box<dyn Error>> {
+    let data = fs::read(file)
+        .map_err(|e| Errors::ImportError(format!("unable to read {}: {}", file, e)))?;
+    let xmp = read_xmp(&data)?;
+    let schedule = parse_schedule(&xmp)?;
+
+    fs::create_dir_all(output)?;
+    let output = fs::canonicalize(output)?;
+    let frames = extract_frames(file, &output.join(".frames"))?;
+
+    let order: Vec<usize> = match &schedule {
+        HeicSchedule::Times(entries) | HeicSchedule::Solar(entries) => {
+            entries.iter().map(|(_, index)| *index).collect()
+        }
+    };
+    //numbered from 1 in the order they are shown, since that's the order the directory is read in
+    for (position, index) in order.iter().enumerate() {
+        let frame = frames.get(*index).ok_or_else(|| {
+            Errors::ImportError(format!("frame {} is missing from {}", index, file))
+        })?;
+        let extension = frame
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("jpg");
+        fs::copy(
+            frame,
+            output.join(format!("{}.{}", position + 1, extension)),
+        )?;
+    }
+    fs::remove_dir_all(output.join(".frames"))?;
+
+    let output = output.to_str().ok_or(Errors::FilePathError)?;
+    let config = config_for(output, &schedule)?;
+    let config_path = format!("{}.toml", output);
+    fs::write(&config_path, &config)?;
+
+    println!(
+        "Imported {} images into {}. The matching settings below have been written to {}, add them to your config file to use them:\n\n{}",
+        order.len(),
+        output,
+        config_path,
+        config
+    );
+    Ok(())
+}
+
+//the options written to the config file, only the ones for the schedule are set
+#[derive(Serialize, Default)]
+struct ImportedConfig<'a> {
+    directory: &'a str,
+    times: Option<Vec<String>>,
+    solar: Option<bool>,
+    elevations: Option<Vec<f64>>,
+}
+
+fn config_for(output: &str, schedule: &HeicSchedule) -> Result<String, Errors> {
+    let config = match schedule {
+        HeicSchedule::Times(entries) => ImportedConfig {
+            directory: output,
+            times: Some(
+                entries
+                    .iter()
+                    .map(|(fraction, _)| {
+                        let time = Time::new((fraction * 1440.0).round() as u32 % 1440);
+                        format!("{:02}:{:02}", time.hours, time.mins)
+                    })
+                    .collect(),
+            ),
+            ..ImportedConfig::default()
+        },
+        HeicSchedule::Solar(entries) => ImportedConfig {
+            directory: output,
+            solar: Some(true),
+            elevations: Some(entries.iter().map(|(elevation, _)| *elevation).collect()),
+            ..ImportedConfig::default()
+        },
+    };
+    let mut config = toml::to_string(&config)
+        .map_err(|e| Errors::ImportError(format!("unable to write the config: {}", e)))?;
+    if let HeicSchedule::Solar(_) = schedule {
+        config += "#lat and long are required with solar\n#lat = 0\n#long = 0\n";
+    }
+    Ok(config)
+}
+
+//runs heif-convert, which writes out every top level image of the file with a number after the
+//name when there are more than one, and returns them in the order they are stored in the file
+fn extract_frames(file: &str, frames_dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    fs::create_dir_all(frames_dir)?;
+    command::run(
+        Command::new("heif-convert")
+            .arg(file)
+            .arg(frames_dir.join("frame.jpg")),
+        "heif-convert",
+    )?;
+
+    let mut frames: Vec<PathBuf> = fs::read_dir(frames_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    frames.sort_by(|a, b| alphanumeric_sort::compare_path(a, b));
+    Ok(frames)
+}
+
+///Reads the schedule out of the XMP metadata of a dynamic wallpaper
+pub fn parse_schedule(xmp: &str) -> Result<HeicSchedule, Errors> {
+    if let Some(solar) = xmp_value(xmp, "apple_desktop:solar") {
+        let plist = decode_plist(&solar)?;
+        let mut entries: Vec<(f64, f64, usize)> = plist_entries(&plist, "si")?
+            .iter()
+            .map(|entry| {
+                Ok((
+                    plist_real(entry, "a")?,
+                    plist_real(entry, "z")?,
+                    plist_real(entry, "i")? as usize,
+                ))
+            })
+            .collect::<Result<_, Errors>>()?;
+        //the azimuth goes from north (0°) through east, south and west back to north over the
+        //day, so sorting by it puts the frames in the order they are shown starting at midnight
+        entries.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(HeicSchedule::Solar(
+            entries
+                .into_iter()
+                .map(|(elevation, _, index)| (elevation, index))
+                .collect(),
+        ))
+    } else if let Some(h24) = xmp_value(xmp, "apple_desktop:h24") {
+        let plist = decode_plist(&h24)?;
+        let mut entries: Vec<(f64, usize)> = plist_entries(&plist, "ti")?
+            .iter()
+            .map(|entry| Ok((plist_real(entry, "t")?, plist_real(entry, "i")? as usize)))
+            .collect::<Result<_, Errors>>()?;
+        entries.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        //two frames landing on the same minute can't both be scheduled
+        entries.dedup_by_key(|(fraction, _)| (*fraction * 1440.0).round() as u32);
+        Ok(HeicSchedule::Times(entries))
+    } else {
+        Err(Errors::ImportError(String::from(
+            "no dynamic wallpaper metadata found",
+        )))
+    }
+}
+
+//the value can be stored either as an attribute or as an element
+fn xmp_value(xmp: &str, name: &str) -> Option<String> {
+    let attribute = format!("{}=\"", name);
+    if let Some(start) = xmp.find(&attribute) {
+        let rest = &xmp[start + attribute.len()..];
+        return rest.find('"').map(|end| rest[..end].trim().to_string());
+    }
+
+    let element = format!("<{}>", name);
+    let start = xmp.find(&element)?;
+    let rest = &xmp[start + element.len()..];
+    rest.find('<').map(|end| rest[..end].trim().to_string())
+}
+
+fn decode_plist(encoded: &str) -> Result<Value, Errors> {
+    let bytes = STANDARD
+        .decode(encoded)
+        .map_err(|e| Errors::ImportError(format!("invalid metadata encoding: {}", e)))?;
+    Value::from_reader(Cursor::new(bytes))
+        .map_err(|e| Errors::ImportError(format!("invalid metadata: {}", e)))
+}
+
+fn plist_entries<'a>(plist: &'a Value, key: &str) -> Result<&'a Vec<Value>, Errors> {
+    plist
+        .as_dictionary()
+        .and_then(|dict| dict.get(key))
+        .and_then(|entries| entries.as_array())
+        .ok_or_else(|| Errors::ImportError(format!("metadata has no {} list", key)))
+}
+
+//numbers may be stored as either integers or reals
+fn plist_real(entry: &Value, key: &str) -> Result<f64, Errors> {
+    let value = entry.as_dictionary().and_then(|dict| dict.get(key));
+    value
+        .and_then(|value| {
+            value
+                .as_real()
+                .or_else(|| value.as_signed_integer().map(|int| int as f64))
+        })
+        .ok_or_else(|| Errors::ImportError(format!("metadata entry is missing {}", key)))
+}
+
+//minimal reader for the boxes of an ISO base media file (which HEIF files are), just enough to
+//find the XMP item in the meta box
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data, pos: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Errors> {
+        if len > self.remaining() {
+            return Err(Errors::ImportError(String::from("file is truncated")));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    //reads a big endian unsigned integer of the given size in bytes
+    fn uint(&mut self, size: usize) -> Result<u64, Errors> {
+        Ok(self
+            .bytes(size)?
+            .iter()
+            .fold(0, |acc, byte| (acc << 8) | *byte as u64))
+    }
+
+    fn string(&mut self) -> Result<String, Errors> {
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        let len = rest
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(rest.len());
+        let string = String::from_utf8_lossy(&rest[..len]).to_string();
+        self.pos += (len + 1).min(rest.len());
+        Ok(string)
+    }
+
+    //returns the type and contents of the next box
+    fn next_box(&mut self) -> Result<([u8; 4], &'a [u8]), Errors> {
+        let start = self.pos;
+        let mut size = self.uint(4)?;
+        let mut box_type = [0; 4];
+        box_type.copy_from_slice(self.bytes(4)?);
+        if size == 1 {
+            size = self.uint(8)?;
+        } else if size == 0 {
+            size = (self.data.len() - start) as u64;
+        }
+        let header = self.pos - start;
+        let contents = self.bytes((size as usize).saturating_sub(header))?;
+        Ok((box_type, contents))
+    }
+
+    fn find_box(&mut self, wanted: &[u8; 4]) -> Result<&'a [u8], Errors> {
+        while self.remaining() > 0 {
+            let (box_type, contents) = self.next_box()?;
+            if &box_type == wanted {
+                return Ok(contents);
+            }
+        }
+        Err(Errors::ImportError(format!(
+            "no {} box found",
+            String::from_utf8_lossy(wanted)
+        )))
+    }
+}
+
+struct Extent {
+    offset: u64,
+    length: u64,
+}
+
+struct Location {
+    item_id: u64,
+    construction_method: u64,
+    extents: Vec<Extent>,
+}
+
+///Returns the XMP metadata of a HEIF file
+pub fn read_xmp(data: &[u8]) -> Result<String, Errors> {
+    let meta = Reader::new(data).find_box(b"meta")?;
+    //meta is a full box, with a version and flags before its children
+    let children = meta
+        .get(4..)
+        .ok_or_else(|| Errors::ImportError(String::from("meta box is truncated")))?;
+
+    let xmp_id = xmp_item_id(Reader::new(children).find_box(b"iinf")?)?;
+    let locations = item_locations(Reader::new(children).find_box(b"iloc")?)?;
+    let location = locations
+        .iter()
+        .find(|location| location.item_id == xmp_id)
+        .ok_or_else(|| Errors::ImportError(String::from("XMP metadata has no location")))?;
+
+    //construction method 1 means the offsets are within the idat box rather than the file
+    let source = match location.construction_method {
+        0 => data,
+        1 => Reader::new(children).find_box(b"idat")?,
+        _ => {
+            return Err(Errors::ImportError(String::from(
+                "XMP metadata is stored in an unsupported way",
+            )))
+        }
+    };
+
+    let mut xmp = vec![];
+    for extent in location.extents.iter() {
+        let mut reader = Reader::new(source);
+        reader.pos = extent.offset as usize;
+        let length = if extent.length == 0 {
+            reader.remaining()
+        } else {
+            extent.length as usize
+        };
+        xmp.extend_from_slice(reader.bytes(length)?);
+    }
+    Ok(String::from_utf8_lossy(&xmp).to_string())
+}
+
+fn xmp_item_id(iinf: &[u8]) -> Result<u64, Errors> {
+    let mut reader = Reader::new(iinf);
+    let version = reader.uint(1)?;
+    reader.uint(3)?;
+    let entry_count = reader.uint(if version == 0 { 2 } else { 4 })?;
+
+    for _ in 0..entry_count {
+        let (box_type, infe) = reader.next_box()?;
+        if &box_type != b"infe" {
+            continue;
+        }
+        let mut infe = Reader::new(infe);
+        let version = infe.uint(1)?;
+        infe.uint(3)?;
+        //older versions of the box don't have an item type
+        if version < 2 {
+            continue;
+        }
+        let item_id = infe.uint(if version == 2 { 2 } else { 4 })?;
+        infe.uint(2)?;
+        let item_type = infe.bytes(4)?;
+        infe.string()?;
+        if item_type == b"mime" && infe.string()? == "application/rdf+xml" {
+            return Ok(item_id);
+        }
+    }
+    Err(Errors::ImportError(String::from(
+        "no XMP metadata found in file",
+    )))
+}
+
+fn item_locations(iloc: &[u8]) -> Result<Vec<Location>, Errors> {
+    let mut reader = Reader::new(iloc);
+    let version = reader.uint(1)?;
+    reader.uint(3)?;
+    let sizes = reader.uint(1)?;
+    let (offset_size, length_size) = ((sizes >> 4) as usize, (sizes & 0xf) as usize);
+    let sizes = reader.uint(1)?;
+    let base_offset_size = (sizes >> 4) as usize;
+    let index_size = if version == 1 || version == 2 {
+        (sizes & 0xf) as usize
+    } else {
+        0
+    };
+    let item_count = reader.uint(if version < 2 { 2 } else { 4 })?;
+
+    let mut locations = vec![];
+    for _ in 0..item_count {
+        let item_id = reader.uint(if version < 2 { 2 } else { 4 })?;
+        let construction_method = if version == 1 || version == 2 {
+            reader.uint(2)? & 0xf
+        } else {
+            0
+        };
+        reader.uint(2)?;
+        let base_offset = reader.uint(base_offset_size)?;
+        let extent_count = reader.uint(2)?;
+
+        let mut extents = vec![];
+        for _ in 0..extent_count {
+            reader.uint(index_size)?;
+            let offset = reader.uint(offset_size)?;
+            let length = reader.uint(length_size)?;
+            let offset = base_offset.checked_add(offset).ok_or_else(|| {
+                Errors::ImportError(String::from("XMP metadata has an invalid location"))
+            })?;
+            extents.push(Extent { offset, length });
+        }
+        locations.push(Location {
+            item_id,
+            construction_method,
+            extents,
+        });
+    }
+    Ok(locations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plist::Dictionary;
+
+    fn entry(values: &[(&str, Value)]) -> Value {
+        let mut dict = Dictionary::new();
+        for (key, value) in values.iter() {
+            dict.insert(key.to_string(), value.clone());
+        }
+        Value::Dictionary(dict)
+    }
+
+    //the plist encoded the way apple stores it in the metadata
+    fn encoded(key: &str, entries: Vec<Value>) -> String {
+        let mut bytes = vec![];
+        entry(&[(key, Value::Array(entries))])
+            .to_writer_binary(&mut bytes)
+            .unwrap();
+        STANDARD.encode(bytes)
+    }
+
+    fn xmp(name: &str, value: &str) -> String {
+        format!(
+            r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF><rdf:Description xmlns:apple_desktop="http://ns.apple.com/namespace/1.0/" {}="{}"/></rdf:RDF></x:xmpmeta>"#,
+            name, value
+        )
+    }
+
+    fn h24() -> String {
+        //times are fractions of the day, out of order with one landing on the same minute
+        let times = [(0.75, 2), (0.25, 0), (0.5, 1), (0.0, 3), (0.25001, 4)];
+        let entries = Vec::from(times)
+            .into_iter()
+            .map(|(t, i)| entry(&[("t", Value::Real(t)), ("i", Value::Integer(i.into()))]))
+            .collect();
+        xmp("apple_desktop:h24", &encoded("ti", entries))
+    }
+
+    fn solar() -> String {
+        //altitude, azimuth and frame, in the order they're stored rather than shown
+        let positions = [
+            (-30.0, 0.0, 0),
+            (45.0, 180.0, 1),
+            (10.0, 90.0, 2),
+            (-10.0, 300.0, 3),
+        ];
+        let entries = Vec::from(positions)
+            .into_iter()
+            .map(|(a, z, i)| {
+                entry(&[
+                    ("a", Value::Real(a)),
+                    ("z", Value::Real(z)),
+                    ("i", Value::Integer(i.into())),
+                ])
+            })
+            .collect();
+        xmp("apple_desktop:solar", &encoded("si", entries))
+    }
+
+    fn full_box(kind: &[u8; 4], version: u8, contents: &[u8]) -> Vec<u8> {
+        let mut full = vec![version, 0, 0, 0];
+        full.extend_from_slice(contents);
+        iso_box(kind, &full)
+    }
+
+    fn iso_box(kind: &[u8; 4], contents: &[u8]) -> Vec<u8> {
+        let mut bytes = ((contents.len() + 8) as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(kind);
+        bytes.extend_from_slice(contents);
+        bytes
+    }
+
+    //a HEIF file with an image item and the XMP item, stored in idat, with `base_offset` added
+    //to the XMP's offset
+    fn heif(xmp: &str, base_offset: u64) -> Vec<u8> {
+        let mut infe_image = 1u16.to_be_bytes().to_vec();
+        infe_image.extend_from_slice(&[0, 0]);
+        infe_image.extend_from_slice(b"hvc1\0");
+        let mut infe_xmp = 2u16.to_be_bytes().to_vec();
+        infe_xmp.extend_from_slice(&[0, 0]);
+        infe_xmp.extend_from_slice(b"mime\0application/rdf+xml\0");
+        let mut iinf = 2u16.to_be_bytes().to_vec();
+        iinf.extend(full_box(b"infe", 2, &infe_image));
+        iinf.extend(full_box(b"infe", 2, &infe_xmp));
+
+        //version 1 with 4 byte offsets and lengths, 8 byte base offsets and no index
+        let mut iloc = vec![0x44, 0x80];
+        iloc.extend_from_slice(&1u16.to_be_bytes());
+        iloc.extend_from_slice(&2u16.to_be_bytes());
+        iloc.extend_from_slice(&1u16.to_be_bytes());
+        iloc.extend_from_slice(&0u16.to_be_bytes());
+        iloc.extend_from_slice(&base_offset.to_be_bytes());
+        iloc.extend_from_slice(&1u16.to_be_bytes());
+        iloc.extend_from_slice(&4u32.to_be_bytes());
+        iloc.extend_from_slice(&(xmp.len() as u32).to_be_bytes());
+        let mut idat = b"skip".to_vec();
+        idat.extend_from_slice(xmp.as_bytes());
+
+        let mut meta = full_box(b"hdlr", 0, b"\0\0\0\0pict");
+        meta.extend(full_box(b"iinf", 0, &iinf));
+        meta.extend(full_box(b"iloc", 1, &iloc));
+        meta.extend(iso_box(b"idat", &idat));
+
+        let mut file = iso_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+        file.extend(full_box(b"meta", 0, &meta));
+        file.extend(iso_box(b"mdat", b"frames"));
+        file
+    }
+
+    #[test]
+    fn h24_frames_are_sorted_by_time() {
+        assert_eq!(
+            parse_schedule(&h24()).unwrap(),
+            HeicSchedule::Times(vec![(0.0, 3), (0.25, 0), (0.5, 1), (0.75, 2)])
+        );
+    }
+
+    #[test]
+    fn solar_frames_are_sorted_by_azimuth() {
+        assert_eq!(
+            parse_schedule(&solar()).unwrap(),
+            HeicSchedule::Solar(vec![(-30.0, 0), (10.0, 2), (45.0, 1), (-10.0, 3)])
+        );
+    }
+
+    #[test]
+    fn missing_metadata_is_an_error() {
+        assert!(matches!(
+            parse_schedule(&xmp("apple_desktop:apr", "")),
+            Err(Errors::ImportError(_))
+        ));
+        assert!(matches!(
+            parse_schedule(&xmp("apple_desktop:h24", "not base64!")),
+            Err(Errors::ImportError(_))
+        ));
+    }
+
+    #[test]
+    fn xmp_is_read_from_the_item_in_idat() {
+        let xmp = solar();
+        assert_eq!(read_xmp(&heif(&xmp, 0)).unwrap(), xmp);
+    }
+
+    #[test]
+    fn malformed_files_are_errors() {
+        let xmp = h24();
+        //the extent's offset of 4 can't be added to this
+        assert!(matches!(
+            read_xmp(&heif(&xmp, u64::MAX - 1)),
+            Err(Errors::ImportError(_))
+        ));
+        assert!(matches!(
+            read_xmp(&heif(&xmp, 1 << 40)),
+            Err(Errors::ImportError(_))
+        ));
+        let mut truncated = heif(&xmp, 0);
+        truncated.truncate(truncated.len() / 2);
+        assert!(matches!(read_xmp(&truncated), Err(Errors::ImportError(_))));
+    }
+
+    #[test]
+    fn config_is_written_as_toml() {
+        let schedule = parse_schedule(&h24()).unwrap();
+        let config = config_for(r#"/home/me/My "Walls"\dyn"#, &schedule).unwrap();
+        let parsed: toml::Value = toml::from_str(&config).unwrap();
+        assert_eq!(
+            parsed["directory"].as_str(),
+            Some(r#"/home/me/My "Walls"\dyn"#)
+        );
+        let times: Vec<_> = parsed["times"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|time| time.as_str().unwrap())
+            .collect();
+        assert_eq!(times, ["00:00", "06:00", "12:00", "18:00"]);
+
+        let schedule = parse_schedule(&solar()).unwrap();
+        let config = config_for("/home/me/dyn", &schedule).unwrap();
+        let parsed: toml::Value = toml::from_str(&config).unwrap();
+        assert_eq!(parsed["solar"].as_bool(), Some(true));
+        let elevations: Vec<_> = parsed["elevations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|elevation| elevation.as_float().unwrap())
+            .collect();
+        assert_eq!(elevations, [-30.0, 10.0, 45.0, -10.0]);
+        assert!(config.contains("#lat = 0"));
+    }
+}
@@ -0,0 +1,519 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//what the programs the wallpaper is sent to are told about the change, through placeholders in
+//their arguments and environment variables, and the hooks that only run on certain changes
+use crate::{
+    command::{self, split},
+    errors::Errors,
+};
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use dirs_next::data_dir;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Mutex, RwLock},
+};
+
+//phases where the sun is up, the rest are between sunset and sunrise
+const SUN_UP_PHASES: [&str; 3] = ["golden_morning", "day", "golden_evening"];
+
+//the hooks option, set once the config has been read
+static HOOKS: RwLock<Vec<Hook>> = RwLock::new(Vec::new());
+//the last change the hooks were run for, to tell what's different about the next one
+static LAST: Mutex<Option<Change>> = Mutex::new(None);
+
+///A command from the config file that's run only when its trigger happens, rather than every time
+///the wallpaper is set like the programs option. The image isn't added to the end of the
+///command, so the placeholders need to be used for it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Hook {
+    #[serde(deserialize_with = "crate::config::deserialize_command")]
+    pub command: String,
+    #[serde(default)]
+    pub on: Trigger,
+    ///The image the hook is for, when triggered on a file. Matched against the end of the path,
+    ///so `3.png` or `night/3.png` both work
+    pub file: Option<String>,
+}
+
+///When a hook is run
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Trigger {
+    ///Whenever the image changes to a different one
+    #[default]
+    Change,
+    ///Whenever the phase of the day changes, including when the first wallpaper is set
+    Phase,
+    ///When the phase goes from one where the sun is down to one where it is up
+    Sunrise,
+    ///When the phase goes from one where the sun is up to one where it is down
+    Sunset,
+    ///The first time the wallpaper is set each day, even across restarts
+    Daily,
+    ///When the image changes to the one in the hook's file option
+    File,
+}
+
+///Replaces the hooks to run. `None` removes them all
+pub fn set_hooks(hooks: Option<Vec<Hook>>) {
+    *HOOKS.write().expect("Hooks poisoned") = hooks.unwrap_or_default();
+}
+
+///Runs the hooks triggered by going from the last change to this one. A hook failing doesn't stop
+///the wallpaper from changing, so errors are printed rather than returned
+pub fn run(change: &Change) {
+    let hooks = HOOKS.read().expect("Hooks poisoned").clone();
+    let last = LAST
+        .lock()
+        .expect("Last change poisoned")
+        .replace(change.clone());
+    run_hooks(
+        &hooks,
+        last.as_ref(),
+        change,
+        &daily_path(),
+        Local::now().date_naive(),
+    );
+}
+
+//runs the hooks, saving to `daily` that the daily hooks ran `today` once they've all worked, so a
+//failed one is tried again on the next change
+fn run_hooks(
+    hooks: &[Hook],
+    last: Option<&Change>,
+    change: &Change,
+    daily: &Path,
+    today: NaiveDate,
+) {
+    let first_today = hooks.iter().any(|hook| hook.on == Trigger::Daily) && !ran_on(daily, today);
+    let mut daily_done = first_today;
+
+    for hook in hooks.iter() {
+        if !hook.triggered_by(last, change, first_today) {
+            continue;
+        }
+        match prepare(&hook.command, change)
+            .and_then(|mut cmd| command::run(&mut cmd, &hook.command))
+        {
+            Ok(()) => println!("Ran the hook {}", hook.command),
+            Err(e) => {
+                daily_done &= hook.on != Trigger::Daily;
+                eprintln!("{}", e);
+            }
+        }
+    }
+
+    if daily_done {
+        if let Err(e) = fs::write(daily, today.to_string()) {
+            eprintln!("Unable to save when the daily hooks ran: {}", e);
+        }
+    }
+}
+
+impl Hook {
+    fn triggered_by(&self, last: Option<&Change>, change: &Change, first_today: bool) -> bool {
+        let changed = last.is_none_or(|last| last.wallpaper != change.wallpaper);
+        let last_phase = last.and_then(|last| last.phase.as_deref());
+        let sun_up = |phase: &str| SUN_UP_PHASES.contains(&phase);
+        match (self.on, last_phase, change.phase.as_deref()) {
+            (Trigger::Change, ..) => changed,
+            (Trigger::Phase, last, Some(phase)) => last != Some(phase),
+            (Trigger::Sunrise, Some(last), Some(phase)) => !sun_up(last) && sun_up(phase),
+            (Trigger::Sunset, Some(last), Some(phase)) => sun_up(last) && !sun_up(phase),
+            (Trigger::Daily, ..) => first_today,
+            (Trigger::File, ..) => {
+                changed
+                    && self
+                        .file
+                        .as_ref()
+                        .is_some_and(|file| Path::new(&change.wallpaper).ends_with(file))
+            }
+            _ => false,
+        }
+    }
+}
+
+///Splits the command and fills in the placeholders and environment variables for this change
+pub fn prepare(command: &str, change: &Change) -> Result<Command, Errors> {
+    let mut words = split(command)?.into_iter();
+    let mut prepared = Command::new(words.next().unwrap());
+    prepared.envs(change.env());
+    for word in words {
+        prepared.arg(change.substitute(&word));
+    }
+    Ok(prepared)
+}
+
+//the date the daily hooks last ran on is kept in the data directory, so restarting doesn't run
+//them again
+fn daily_path() -> PathBuf {
+    match data_dir() {
+        Some(dir) => dir.join("dyn-wall-rs").join("daily"),
+        None => PathBuf::from("dyn-wall-rs-daily"),
+    }
+}
+
+fn ran_on(daily: &Path, today: NaiveDate) -> bool {
+    let last_run = fs::read_to_string(daily)
+        .ok()
+        .and_then(|date| date.trim().parse::<NaiveDate>().ok());
+    last_run == Some(today)
+}
+
+///Details of a wallpaper change, passed on to the programs. Anything that isn't known (like the
+///next image when they're picked at random) is left empty
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Change {
+    ///The image being set
+    pub wallpaper: String,
+    ///The image before it in the schedule, or the one shown before it when changing every few days
+    pub prev: Option<String>,
+    ///The image after it in the schedule
+    pub next: Option<String>,
+    ///When the wallpaper changes next
+    pub next_time: Option<NaiveDateTime>,
+    ///Position of the image among the images, starting at 1
+    pub index: usize,
+    ///How many images there are
+    pub count: usize,
+    ///The phase of the day, when syncing to the sun. This is the name of the phase directory the
+    ///image is in, or day or night when the images follow the height of the sun
+    pub phase: Option<String>,
+    ///The monitor option
+    pub monitor: Option<String>,
+}
+
+impl Change {
+    ///A change with nothing known other than the image being set
+    pub fn new(wallpaper: &str) -> Self {
+        Change {
+            wallpaper: wallpaper.to_string(),
+            ..Change::default()
+        }
+    }
+
+    //each placeholder, the environment variable it's also set as, and its value. !NEXT_TIME comes
+    //before !NEXT so the longer name is matched first
+    fn values(&self) -> [(&'static str, &'static str, String); 8] {
+        let optional = |value: &Option<String>| value.to_owned().unwrap_or_default();
+        [
+            ("!WALL", "DYN_WALL_WALLPAPER", self.wallpaper.to_owned()),
+            ("!PREV", "DYN_WALL_PREV", optional(&self.prev)),
+            (
+                "!NEXT_TIME",
+                "DYN_WALL_NEXT_TIME",
+                self.next_time
+                    .map(|time| time.format("%Y-%m-%dT%H:%M:%S").to_string())
+                    .unwrap_or_default(),
+            ),
+            ("!NEXT", "DYN_WALL_NEXT", optional(&self.next)),
+            ("!INDEX", "DYN_WALL_INDEX", self.index.to_string()),
+            ("!COUNT", "DYN_WALL_COUNT", self.count.to_string()),
+            ("!PHASE", "DYN_WALL_PHASE", optional(&self.phase)),
+            ("!MONITOR", "DYN_WALL_MONITOR", optional(&self.monitor)),
+        ]
+    }
+
+    ///Replaces the placeholders anywhere in `word`, so `--image=!WALL` works as well as `!WALL`
+    ///on its own. Values aren't searched for placeholders again, in case a path contains one
+    pub fn substitute(&self, word: &str) -> String {
+        let values = self.values();
+        let mut substituted = String::new();
+        let mut rest = word;
+        while let Some(start) = rest.find('!') {
+            substituted.push_str(&rest[..start]);
+            rest = &rest[start..];
+            match values
+                .iter()
+                .find(|(placeholder, ..)| rest.starts_with(placeholder))
+            {
+                Some((placeholder, _, value)) => {
+                    substituted.push_str(value);
+                    rest = &rest[placeholder.len()..];
+                }
+                None => {
+                    substituted.push('!');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        substituted.push_str(rest);
+        substituted
+    }
+
+    ///The values as `DYN_WALL_*` environment variables
+    pub fn env(&self) -> Vec<(&'static str, String)> {
+        Vec::from(self.values())
+            .into_iter()
+            .map(|(_, name, value)| (name, value))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn hook(on: Trigger) -> Hook {
+        Hook {
+            command: String::from("true"),
+            on,
+            file: None,
+        }
+    }
+
+    fn change(wallpaper: &str, phase: Option<&str>) -> Change {
+        Change {
+            phase: phase.map(String::from),
+            ..Change::new(wallpaper)
+        }
+    }
+
+    #[test]
+    fn sunrise_and_sunset_trigger_between_up_and_down_phases() {
+        let night = change("/walls/night/1.png", Some("night"));
+        let dawn = change("/walls/dawn/1.png", Some("dawn"));
+        let golden = change("/walls/golden_morning/1.png", Some("golden_morning"));
+        let day = change("/walls/day/1.png", Some("day"));
+        let dusk = change("/walls/dusk/1.png", Some("dusk"));
+
+        let sunrise = hook(Trigger::Sunrise);
+        assert!(sunrise.triggered_by(Some(&dawn), &golden, false));
+        assert!(sunrise.triggered_by(Some(&night), &day, false));
+        assert!(!sunrise.triggered_by(Some(&night), &dawn, false));
+        assert!(!sunrise.triggered_by(Some(&golden), &day, false));
+        assert!(!sunrise.triggered_by(Some(&day), &dusk, false));
+        //nothing to compare against for the first wallpaper
+        assert!(!sunrise.triggered_by(None, &day, false));
+
+        let sunset = hook(Trigger::Sunset);
+        assert!(sunset.triggered_by(Some(&day), &dusk, false));
+        assert!(!sunset.triggered_by(Some(&dusk), &night, false));
+        assert!(!sunset.triggered_by(Some(&dawn), &golden, false));
+        assert!(!sunset.triggered_by(None, &night, false));
+
+        //without phases, neither can tell
+        let plain = change("/walls/1.png", None);
+        assert!(!sunrise.triggered_by(Some(&plain), &plain, false));
+        assert!(!sunset.triggered_by(Some(&plain), &plain, false));
+    }
+
+    #[test]
+    fn phase_triggers_when_the_phase_changes() {
+        let phase = hook(Trigger::Phase);
+        let day = change("/walls/day/1.png", Some("day"));
+        let later = change("/walls/day/2.png", Some("day"));
+        let dusk = change("/walls/dusk/1.png", Some("dusk"));
+        assert!(phase.triggered_by(None, &day, false));
+        assert!(phase.triggered_by(Some(&day), &dusk, false));
+        assert!(!phase.triggered_by(Some(&day), &later, false));
+        assert!(!phase.triggered_by(None, &change("/walls/1.png", None), false));
+    }
+
+    #[test]
+    fn change_and_file_trigger_on_a_different_image() {
+        let first = change("/walls/night/3.png", None);
+        let second = change("/walls/night/4.png", None);
+
+        let on_change = hook(Trigger::Change);
+        assert!(on_change.triggered_by(None, &first, false));
+        assert!(on_change.triggered_by(Some(&first), &second, false));
+        assert!(!on_change.triggered_by(Some(&first), &first, false));
+
+        let on_file = |file: &str| Hook {
+            file: Some(String::from(file)),
+            ..hook(Trigger::File)
+        };
+        assert!(on_file("3.png").triggered_by(Some(&second), &first, false));
+        assert!(on_file("night/3.png").triggered_by(None, &first, false));
+        assert!(!on_file("day/3.png").triggered_by(None, &first, false));
+        //only the end of the path is matched, a whole component at a time
+        assert!(!on_file("t/3.png").triggered_by(None, &first, false));
+        assert!(!on_file("4.png").triggered_by(Some(&second), &first, false));
+        assert!(!on_file("3.png").triggered_by(Some(&first), &first, false));
+        assert!(!hook(Trigger::File).triggered_by(None, &first, false));
+    }
+
+    #[test]
+    fn daily_triggers_on_the_first_run_of_the_day() {
+        let daily = hook(Trigger::Daily);
+        let first = change("/walls/1.png", None);
+        assert!(daily.triggered_by(Some(&first), &first, true));
+        assert!(!daily.triggered_by(None, &first, false));
+    }
+
+    #[test]
+    fn daily_hooks_are_only_recorded_once_they_work() {
+        let dir = env::temp_dir().join(format!("dyn-wall-rs-daily-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let daily = dir.join("daily");
+        let marker = dir.join("ran");
+        let today = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        let wallpaper = change("/walls/1.png", None);
+        let daily_hook = |command: String| Hook {
+            command,
+            ..hook(Trigger::Daily)
+        };
+
+        run_hooks(
+            &[daily_hook(String::from("false"))],
+            None,
+            &wallpaper,
+            &daily,
+            today,
+        );
+        assert!(!ran_on(&daily, today));
+
+        let touch = daily_hook(format!("touch {}", marker.display()));
+        run_hooks(
+            std::slice::from_ref(&touch),
+            None,
+            &wallpaper,
+            &daily,
+            today,
+        );
+        assert!(ran_on(&daily, today));
+        assert!(marker.exists());
+
+        //already done today, so it isn't run again
+        fs::remove_file(&marker).unwrap();
+        run_hooks(
+            std::slice::from_ref(&touch),
+            None,
+            &wallpaper,
+            &daily,
+            today,
+        );
+        assert!(!marker.exists());
+
+        let tomorrow = today.succ_opt().unwrap();
+        run_hooks(&[touch], None, &wallpaper, &daily, tomorrow);
+        assert!(marker.exists());
+        assert!(ran_on(&daily, tomorrow));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn full_change() -> Change {
+        Change {
+            wallpaper: String::from("/walls/day/2.png"),
+            prev: Some(String::from("/walls/day/1.png")),
+            next: Some(String::from("/walls/dusk/1.png")),
+            next_time: NaiveDate::from_ymd_opt(2024, 3, 10)
+                .unwrap()
+                .and_hms_opt(18, 5, 0),
+            index: 2,
+            count: 7,
+            phase: Some(String::from("day")),
+            monitor: Some(String::from("DP-1")),
+        }
+    }
+
+    #[test]
+    fn every_placeholder_is_substituted() {
+        let change = full_change();
+        let substitute = |word: &str| change.substitute(word);
+        assert_eq!(substitute("!WALL"), "/walls/day/2.png");
+        assert_eq!(substitute("!PREV"), "/walls/day/1.png");
+        assert_eq!(substitute("!NEXT"), "/walls/dusk/1.png");
+        assert_eq!(substitute("!NEXT_TIME"), "2024-03-10T18:05:00");
+        assert_eq!(
+            substitute("!NEXT_TIME!NEXT"),
+            "2024-03-10T18:05:00/walls/dusk/1.png"
+        );
+        assert_eq!(substitute("!INDEX/!COUNT"), "2/7");
+        assert_eq!(substitute("--phase=!PHASE"), "--phase=day");
+        assert_eq!(substitute("!MONITOR:!WALL"), "DP-1:/walls/day/2.png");
+    }
+
+    #[test]
+    fn unknown_placeholders_are_left_alone() {
+        let change = full_change();
+        assert_eq!(change.substitute("!"), "!");
+        assert_eq!(change.substitute("wow!"), "wow!");
+        assert_eq!(change.substitute("!!WALL!"), "!/walls/day/2.png!");
+        assert_eq!(
+            change.substitute("!wall !NEXTS"),
+            "!wall /walls/dusk/1.pngS"
+        );
+        //values aren't searched for placeholders again
+        let odd = Change::new("/walls/!PREV.png");
+        assert_eq!(odd.substitute("!WALL"), "/walls/!PREV.png");
+    }
+
+    #[test]
+    fn unknown_values_are_empty() {
+        let change = Change::new("/walls/1.png");
+        assert_eq!(
+            change.substitute("[!PREV|!NEXT|!NEXT_TIME|!PHASE|!MONITOR]"),
+            "[||||]"
+        );
+        assert_eq!(change.substitute("!INDEX of !COUNT"), "0 of 0");
+    }
+
+    #[test]
+    fn values_are_passed_in_the_environment() {
+        let env = full_change().env();
+        let expected = [
+            ("DYN_WALL_WALLPAPER", "/walls/day/2.png"),
+            ("DYN_WALL_PREV", "/walls/day/1.png"),
+            ("DYN_WALL_NEXT_TIME", "2024-03-10T18:05:00"),
+            ("DYN_WALL_NEXT", "/walls/dusk/1.png"),
+            ("DYN_WALL_INDEX", "2"),
+            ("DYN_WALL_COUNT", "7"),
+            ("DYN_WALL_PHASE", "day"),
+            ("DYN_WALL_MONITOR", "DP-1"),
+        ];
+        let env: Vec<(&str, &str)> = env
+            .iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .collect();
+        assert_eq!(env, expected);
+
+        //unknown values are still set, just empty
+        let empty = Change::new("/walls/1.png").env();
+        assert_eq!(empty.len(), expected.len());
+        assert!(empty.contains(&("DYN_WALL_NEXT", String::new())));
+        assert!(empty.contains(&("DYN_WALL_INDEX", String::from("0"))));
+    }
+
+    #[test]
+    fn prepared_commands_get_the_values() {
+        let dir = env::temp_dir().join(format!("dyn-wall-rs-prepare-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("out");
+        let command = format!(
+            "sh -c 'echo \"$1 $DYN_WALL_PHASE $DYN_WALL_NEXT_TIME\" > {}' sh !WALL",
+            out.display()
+        );
+        let mut prepared = prepare(&command, &full_change()).unwrap();
+        assert!(prepared.status().unwrap().success());
+        assert_eq!(
+            fs::read_to_string(&out).unwrap(),
+            "/walls/day/2.png day 2024-03-10T18:05:00\n"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
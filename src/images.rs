@@ -0,0 +1,195 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//deciding which files in a wallpaper directory are images, so stray files like .DS_Store,
+//READMEs or thumbnail caches aren't counted or set as the wallpaper
+use std::{fs::File, io::Read, mem, path::Path, sync::RwLock};
+
+///Extensions treated as images when the extensions config option isn't set
+pub const DEFAULT_EXTENSIONS: [&str; 13] = [
+    "jpg", "jpeg", "png", "gif", "bmp", "webp", "tif", "tiff", "heic", "heif", "avif", "jxl", "svg",
+];
+
+//the extensions config option, set once the config has been read
+static EXTENSIONS: RwLock<Option<Vec<String>>> = RwLock::new(None);
+
+fn normalise(extensions: Option<Vec<String>>) -> Option<Vec<String>> {
+    extensions.map(|extensions| {
+        extensions
+            .iter()
+            .map(|ext| ext.trim_start_matches('.').to_lowercase())
+            .collect()
+    })
+}
+
+///Replaces the extensions treated as images. `None` goes back to `DEFAULT_EXTENSIONS`
+pub fn set_extensions(extensions: Option<Vec<String>>) {
+    *EXTENSIONS.write().expect("Extension list poisoned") = normalise(extensions);
+}
+
+///Runs `f` with `extensions` treated as images, then goes back to the ones from before. Used to
+///check new options before switching to them, from the thread that looks through the directory
+pub fn with_extensions<T>(extensions: Option<Vec<String>>, f: impl FnOnce() -> T) -> T {
+    let old = mem::replace(
+        &mut *EXTENSIONS.write().expect("Extension list poisoned"),
+        normalise(extensions),
+    );
+    let result = f();
+    *EXTENSIONS.write().expect("Extension list poisoned") = old;
+    result
+}
+
+///Returns true if the path is a visible file that is an image. Files are matched on their
+///extension, and files without one are recognised from their first few bytes instead
+pub fn is_image(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'));
+    if hidden || !path.is_file() {
+        return false;
+    }
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => {
+            let ext = ext.to_lowercase();
+            match EXTENSIONS.read().expect("Extension list poisoned").as_ref() {
+                Some(extensions) => extensions.contains(&ext),
+                None => DEFAULT_EXTENSIONS.contains(&ext.as_str()),
+            }
+        }
+        None => sniff(path),
+    }
+}
+
+//sizes of the headers that can follow the file header of a bmp, one for each version
+const BMP_HEADER_SIZES: [u32; 7] = [12, 40, 52, 56, 64, 108, 124];
+
+//checks the start of the file for the signature of a common image format
+fn sniff(path: &Path) -> bool {
+    let mut header = [0; 18];
+    let read = File::open(path).and_then(|mut file| {
+        let len = file.read(&mut header)?;
+        Ok((len, file.metadata()?.len()))
+    });
+    let (header, file_len) = match read {
+        Ok((len, file_len)) => (&header[..len], file_len),
+        Err(_) => return false,
+    };
+
+    header.starts_with(&[0xff, 0xd8, 0xff])
+        || header.starts_with(b"\x89PNG")
+        || header.starts_with(b"GIF8")
+        || is_bmp(header, file_len)
+        || header.starts_with(b"II*\0")
+        || header.starts_with(b"MM\0*")
+        || header.starts_with(&[0xff, 0x0a])
+        || (header.starts_with(b"RIFF") && header.get(8..12) == Some(b"WEBP"))
+        //heif, heic and avif all use the ISO base media format, which starts with an ftyp box
+        || header.get(4..8) == Some(b"ftyp")
+}
+
+//"BM" is too short to go on by itself, so the size of the file and of the header after it have to
+//be right too
+fn is_bmp(header: &[u8], file_len: u64) -> bool {
+    let field = |at: usize| {
+        header
+            .get(at..at + 4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+    header.starts_with(b"BM")
+        && field(2).map(u64::from) == Some(file_len)
+        && field(14).is_some_and(|size| BMP_HEADER_SIZES.contains(&size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, path::PathBuf};
+
+    //a bmp file header and the start of the header after it, for a file of `len` bytes
+    fn bmp(len: u32, header_size: u32) -> Vec<u8> {
+        let mut bmp = b"BM".to_vec();
+        bmp.extend(len.to_le_bytes());
+        bmp.extend([0; 4]);
+        bmp.extend(54u32.to_le_bytes());
+        bmp.extend(header_size.to_le_bytes());
+        bmp.resize(len as usize, 0);
+        bmp
+    }
+
+    fn files(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("dyn-wall-rs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("folder.png")).unwrap();
+        let files: [(&str, Vec<u8>); 10] = [
+            ("1.png", vec![]),
+            ("2.JPG", vec![]),
+            ("3.jpg", vec![]),
+            (".hidden.png", b"\x89PNG\r\n".to_vec()),
+            ("notes.txt", b"\x89PNG\r\n".to_vec()),
+            ("photo", b"\x89PNG\r\n\x1a\n".to_vec()),
+            ("bitmap", bmp(70, 40)),
+            ("Bmore", b"BMore notes than anyone needs".to_vec()),
+            ("wrong size", bmp(70, 40)[..60].to_vec()),
+            ("README", b"Just some text".to_vec()),
+        ];
+        for (name, contents) in Vec::from(files) {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn images_are_found_by_extension_or_contents() {
+        let dir = files("images");
+        let image = |name: &str| is_image(&dir.join(name));
+        //extensions are matched without caring about case, without looking inside
+        assert!(image("1.png"));
+        assert!(image("2.JPG"));
+        assert!(!image("notes.txt"));
+        //hidden files and directories are skipped, whatever they're called
+        assert!(!image(".hidden.png"));
+        assert!(!image("folder.png"));
+        assert!(!image("missing.png"));
+        //files without an extension are checked for an image signature
+        assert!(image("photo"));
+        assert!(image("bitmap"));
+        assert!(!image("Bmore"));
+        assert!(!image("wrong size"));
+        assert!(!image("README"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn extensions_can_be_replaced() {
+        let dir = files("extensions");
+        let image = |name: &str| is_image(&dir.join(name));
+        //png is kept so the directories other tests look through aren't affected
+        let (txt, jpg, sniffed) = with_extensions(Some(vec![".TXT".into(), "png".into()]), || {
+            (image("notes.txt"), image("3.jpg"), image("photo"))
+        });
+        assert!(txt);
+        assert!(!jpg);
+        assert!(sniffed);
+        assert!(!image("notes.txt"));
+        assert!(image("3.jpg"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -18,56 +18,87 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 use crate::{
+    backend::SetOptions,
     config::Args,
+    control::{ControlCommand, Request, Status},
     errors::{ConfigFileErrors, Errors},
+    hooks::Change,
     time_track::Time,
 };
-use chrono::{Local, Timelike, Utc};
-use clokwerk::{Scheduler, TimeUnits};
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+use clap::Parser;
 use dirs_next::data_dir;
-use std::{env, error::Error, process, process::Command, sync::Arc, thread::sleep, time::Duration};
+use scheduler::{
+    current_index, minute_of_day, next_change, Clock, Interval, Scheduler, SystemClock, Wake,
+};
+use solar::{Crossing, Direction, Twilight, GOLDEN_HOUR_ANGLE};
+use std::{
+    env,
+    error::Error,
+    fs::TryLockError,
+    iter,
+    path::Path,
+    process,
+    process::Command,
+    sync::{
+        mpsc::{self, Receiver},
+        Arc,
+    },
+};
 use std::{
     fs,
     fs::create_dir_all,
     fs::File,
     fs::OpenOptions,
     io::{Read, Write},
+    thread,
 };
 use walkdir::{DirEntry, WalkDir};
 
-use clokwerk::Job;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
-use run_script::ScriptOptions;
-use unicase::UniCase;
-
-#[cfg(not(windows))]
-use std::env::consts::ARCH;
-
-//crates used to change windows wallpaper
-#[cfg(windows)]
-use std::ffi::OsStr;
-#[cfg(windows)]
-use std::{io, iter, os::raw::c_void, os::windows::ffi::OsStrExt};
-#[cfg(windows)]
-use winapi::um::winuser::{
-    SystemParametersInfoW, SPIF_SENDCHANGE, SPIF_UPDATEINIFILE, SPI_SETDESKWALLPAPER,
-};
 
+pub mod backend;
+pub mod command;
 pub mod config;
+pub mod control;
+#[cfg(feature = "dbus")]
+pub mod dbus;
 pub mod errors;
+pub mod heic;
+pub mod hooks;
+pub mod images;
+pub mod scheduler;
+pub mod slideshow;
+pub mod solar;
 pub mod time_track;
+pub mod watch;
+
+///Directories of images used when syncing to the sun, in the order they happen starting at
+///sunrise
+pub const PHASES: [&str; 6] = [
+    "golden_morning",
+    "day",
+    "golden_evening",
+    "dusk",
+    "night",
+    "dawn",
+];
+const DAY_PHASE: usize = 1;
+const NIGHT_PHASE: usize = 4;
+
+//how many times in a row setting the wallpaper can fail before giving up, when the max_failures
+//option isn't set
+const DEFAULT_MAX_FAILURES: u32 = 5;
+//delay before the first retry, which doubles after each failure up to the maximum
+const RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+const MAX_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(60);
 
 const FULL_DAY: Time = Time {
     hours: 24,
     mins: 0,
     total_mins: 1440,
 };
-const MIDNIGHT: Time = Time {
-    hours: 0,
-    mins: 0,
-    total_mins: 0,
-};
 
 pub fn wallpaper_current_time(
     dir: &str,
@@ -76,180 +107,585 @@ pub fn wallpaper_current_time(
     backend: Arc<Option<String>>,
     min_depth: usize,
 ) -> Result<(), Box<dyn Error>> {
-    let dir_iter = sorted_dir_iter(dir, min_depth);
-    let dir_count = sorted_dir_iter(dir, min_depth);
+    let files = schedule_files(dir, min_depth)?;
+    let curr_time = minute_of_day(Local::now().naive_local());
+    let filepath_set = wallpaper_at(&files, times, curr_time)?;
+    set_wallpaper(&filepath_set, progs, backend)
+}
 
-    let dir_count: usize = dir_count.count();
-    let mut commands_vec: Vec<Command> = vec![];
-    let mut times_iter = times.iter();
-    let curr_time = Time::new(Local::now().hour() * 60 + Local::now().minute());
-    let loop_time = times_iter.next();
-    let mut next_time = times_iter.next().unwrap_or(&FULL_DAY);
-    let mut filepath_set: String = String::new();
-    let mut last_image = String::new();
-    let first_time = times[0];
+///Returns the path of the image that is scheduled to be the wallpaper at `curr_time`, with the
+///files matched up with the times in order
+pub fn wallpaper_at(
+    files: &[String],
+    times: &[Time],
+    curr_time: Time,
+) -> Result<String, Box<dyn Error>> {
+    error_checking(times, times.first(), files.len(), None)?;
 
-    let mut loop_time = error_checking(times, loop_time, dir_count, None)?;
+    Ok(files[current_index(times, curr_time)].to_string())
+}
 
-    //this loop is to find where the current time lays, and adjust the wallpaper based on that
-    for file in dir_iter {
-        //needed for the case where midnight is passed over in the middle of the stated times
-        if loop_time > *next_time {
-            loop_time = MIDNIGHT;
+///Everything the listener needs to run, worked out from the options
+pub struct Setup {
+    ///Directory the images are in. For a slideshow, this is the directory the slideshow is in
+    pub dir: String,
+    pub args: Args,
+    ///How many levels into the directory the images are. 2 when syncing to the sun, since the
+    ///images are in the phase directories, otherwise 1
+    pub min_depth: usize,
+    ///The command line arguments the options came from, so the config file can be merged with
+    ///them again when reloading
+    pub cli: Vec<String>,
+}
+
+///Works out the timings from the number of images when they aren't given, and prepares the data
+///files, for the options given on the command line and in the config file. `cli` is the command
+///line the options came from
+pub fn setup(mut args: Args, cli: Vec<String>) -> Result<Setup, Box<dyn Error>> {
+    //elevation based images all sit in the one directory
+    let min_depth = if args.lat.is_some() && args.long.is_some() && !args.solar {
+        2
+    } else {
+        1
+    };
+
+    let dir = args
+        .directory
+        .to_owned()
+        .ok_or("Error: Directory needs to be specified")?;
+    let dir_count = image_count(&dir, min_depth);
+    let dir = fs::canonicalize(dir)?;
+    let dir = dir.to_str().ok_or(Errors::FilePathError)?;
+    create_data_file("curr")?;
+
+    if args.days.is_some() {
+        args.times = Some(vec![Time::default()]);
+        if create_data_file("visited_days")? || env::var("DYN_TEST").is_ok() {
+            update_wallpaper_days(dir)?;
+        }
+    } else if args.times.is_none() {
+        if dir_count == 0 || 1440 % dir_count != 0 {
+            return Err(Errors::CountCompatError(dir_count).into());
         }
+        let (step_time, mut loop_time) = auto_time_setup(dir);
+        let step_time = step_time?;
+        let mut times: Vec<Time> = vec![];
+        for _ in 1..=dir_count {
+            times.push(loop_time);
+            loop_time += step_time;
+        }
+        args.times = Some(times);
+    }
 
-        let filepath_temp = file.map_err(|_| Errors::FilePathError)?;
-        let filepath_temp = filepath_temp.path();
+    //a slideshow's images are found relative to the folder it's in
+    let dir = match Path::new(dir).parent() {
+        Some(parent) if Path::new(dir).is_file() => parent.to_str().unwrap_or(dir),
+        _ => dir,
+    };
+    //checked up front so a bad reload leaves the running schedule alone
+    if let (None, Some(times)) = (args.days, &args.times) {
+        let file_count = match &args.files {
+            Some(files) => files.len(),
+            None => schedule_files(dir, min_depth)?.len(),
+        };
+        error_checking(times, times.first(), file_count, None)?;
+    }
 
-        let last_image_temp = filepath_temp.to_str().unwrap();
-        last_image = last_image_temp.to_owned();
+    Ok(Setup {
+        dir: dir.to_string(),
+        args,
+        min_depth,
+        cli,
+    })
+}
 
-        if curr_time >= loop_time && curr_time < *next_time {
-            filepath_set.push_str(match filepath_temp.to_str() {
-                Some(filepath) => Ok(filepath),
-                None => Err(Errors::FilePathError),
-            }?);
+//parses arguments passed on from a second instance, as if they had been given on the command line
+//in `cwd`. The directory is made absolute rather than moving into `cwd`, so a reload later on
+//still finds it
+fn setup_from_cli(cwd: &str, cli: Vec<String>) -> Result<Setup, Box<dyn Error>> {
+    let cli = if cwd.is_empty() {
+        cli
+    } else {
+        config::absolute_directory(Path::new(cwd), cli)
+    };
+    let cli_args =
+        Args::try_parse_from(iter::once(String::from("dyn-wall-rs")).chain(cli.iter().cloned()))?;
+    let args = Args::mixed(cli_args, !cli.is_empty())?;
+    images::with_extensions(args.extensions.clone(), || setup(args, cli))
+}
 
-            //this is to send the file as an argument to the user specified program, if one was specified
-            commands_vec_loader(&filepath_set, Arc::clone(&progs), &mut commands_vec);
+//works out the options to start the listener over with, for the commands that change them
+fn new_options(command: &ControlCommand, cli: &[String]) -> Option<Result<Setup, Box<dyn Error>>> {
+    match command {
+        ControlCommand::Reload => Some(setup_from_cli("", cli.to_vec())),
+        ControlCommand::Args { cwd, args } => Some(setup_from_cli(cwd, args.to_vec())),
+        _ => None,
+    }
+}
+
+///Takes the lock that stops two instances from changing the wallpaper at the same time, holding
+///it until the returned file is dropped. The process id is written to the lock file so it can be
+///shown if another instance tries to start
+pub fn lock_instance() -> Result<File, Box<dyn Error>> {
+    let data_dir = data_dir().ok_or(Errors::FilePathError)?;
+    let data_dir = data_dir.to_str().ok_or(Errors::FilePathError)?;
+    create_dir_all(format!("{}/dyn-wall-rs", data_dir))?;
+    lock(&format!("{}/dyn-wall-rs/lock", data_dir))
+}
+
+fn lock(lock_path: &str) -> Result<File, Box<dyn Error>> {
+    let mut lock_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path)?;
+    match lock_file.try_lock() {
+        Ok(()) => {
+            lock_file.set_len(0)?;
+            write!(lock_file, "{}", process::id())?;
+            Ok(lock_file)
         }
-        loop_time = *next_time;
-        next_time = times_iter.next().unwrap_or(&first_time);
+        //the lock is mandatory on windows, so the pid can't be read there
+        Err(TryLockError::WouldBlock) => Err(Errors::AlreadyRunningError(
+            fs::read_to_string(lock_path)
+                .ok()
+                .map(|pid| pid.trim().to_string())
+                .filter(|pid| !pid.is_empty()),
+        )
+        .into()),
+        Err(TryLockError::Error(e)) => Err(e.into()),
     }
+}
 
-    //this is for the edge case where the current time is after the last time specified for the day, but before the first one specified for the day
-    //in that case, the previous loop would push nothing to filepath_set, and so nothing would be sent to feh
-    //what we want in this situation is for the file that is associated with the last time of the day to be sent as an argument to feh,
-    //and to the user specified program
-    if filepath_set.is_empty() {
-        de_command_spawn(&last_image, backend)?;
+///Keeps the wallpaper in line with the schedule until the process is stopped, taking commands
+///from the control socket (and D-Bus) along the way
+pub fn wallpaper_listener(setup: Setup) -> Result<(), Box<dyn Error>> {
+    let (requests, mut commands) = mpsc::channel();
+    //the wallpaper still changes without these, it just can't be controlled
+    #[cfg(feature = "dbus")]
+    if let Err(e) = dbus::serve(requests.clone()) {
+        eprintln!("Unable to start the D-Bus service: {}", e);
+    }
+    if let Err(e) = watch::watch_config(requests.clone()) {
+        eprintln!("Unable to watch the config file for changes: {}", e);
+    }
+    if let Err(e) = watch::reload_on_hangup(requests.clone()) {
+        eprintln!("Unable to listen for SIGHUP: {}", e);
+    }
+    if let Err(e) = control::serve(requests.clone()) {
+        eprintln!("{}", e);
+    }
 
-        commands_vec_loader(&last_image, Arc::clone(&progs), &mut commands_vec);
-        filepath_set = last_image;
-    } else {
-        de_command_spawn(&filepath_set, backend)?;
+    if env::var("DYN_TEST").is_ok() {
+        println!("DAYS = {}", setup.args.days.unwrap_or(1));
     }
 
-    if let Some(progs) = progs.as_deref() {
-        let mut prog_iter = progs.iter();
-        for curr_command in commands_vec.iter_mut() {
-            curr_command
-                .spawn()
-                .map_err(|_| Errors::ProgramRunError(String::from(prog_iter.next().unwrap())))?;
-            println!(
-                "The image {} has been sent as an argument to the specified program",
-                filepath_set
-            );
-        }
+    //the listeners only return when they're asked to start over with new options
+    let mut setup = setup;
+    loop {
+        //replaced along with the setup, in case the directory changes
+        let _dir_watcher = watch::watch_directory(&setup, requests.clone())
+            .map_err(|e| eprintln!("Unable to watch {} for changes: {}", setup.dir, e))
+            .ok();
+        let (new_setup, returned) = if setup.args.days.is_none() {
+            times_listener(setup, commands)?
+        } else {
+            days_listener(setup, commands)?
+        };
+        //the new options have been checked by now, so their settings can be switched to
+        new_setup.args.apply();
+        setup = new_setup;
+        commands = returned.ok_or("The control socket has stopped")?;
     }
-    Ok(())
 }
 
-pub fn wallpaper_listener(dir: String, args: Args, min_depth: usize) -> Result<(), Box<dyn Error>> {
-    let mut scheduler = Scheduler::new();
-    let mut sched_addto;
+//what the listeners hand back when starting over
+type Restart = (Setup, Option<Receiver<Request>>);
+
+fn times_listener(setup: Setup, commands: Receiver<Request>) -> Result<Restart, Box<dyn Error>> {
+    let Setup {
+        dir,
+        args,
+        min_depth,
+        cli,
+    } = setup;
+    //the location options, needed to redo the schedule every day when syncing to the sun
+    let sun_args = Args {
+        lat: args.lat,
+        long: args.long,
+        elevation: args.elevation,
+        twilight: args.twilight,
+        solar: args.solar,
+        elevations: args.elevations.clone(),
+        ..Args::default()
+    };
+    let options = set_options(&args);
     let progs = Arc::new(args.programs);
     let backend = Arc::new(args.backend);
-    let times = args.times.unwrap();
-    let days = args.days;
-    let days_val = days.unwrap_or(1);
-    if env::var("DYN_TEST").is_ok() {
-        println!("DAYS = {}", days_val);
-    }
-
-    if days.is_none() {
-        sched_addto = scheduler.every(1.day()).at("0:00");
-        wallpaper_current_time(
-            &dir,
-            Arc::clone(&progs),
-            &times,
-            Arc::clone(&backend),
-            min_depth,
-        )?;
-
-        for time in &times {
-            let time_fmt = format!("{:02}:{:02}", time.hours, time.mins);
-            sched_addto = sched_addto.and_every(1.day()).at(time_fmt.as_str());
-        }
+    let max_failures = args.max_failures.unwrap_or(DEFAULT_MAX_FAILURES);
+    let mut times = args.times.unwrap();
+    let mut files = match args.files {
+        Some(files) => files,
+        None => schedule_files(&dir, min_depth)?,
+    };
 
-        let sched_closure = move || {
-            let result = wallpaper_current_time(
-                &dir,
-                Arc::clone(&progs),
-                &times,
-                Arc::clone(&backend),
-                min_depth,
-            );
+    let now = Local::now().naive_local();
+    //checks the images and times match up before anything is set
+    wallpaper_at(&files, &times, minute_of_day(now))?;
+    let mut shown = current_index(&times, minute_of_day(now));
+    let change = scheduled_change(&files, &times, shown, now, &sun_args, &options.monitor);
+    //the desktop may never get ready, but the next change is still worth trying
+    if let Err(e) = set_wallpaper_retrying(&change, &progs, &backend, &options, max_failures) {
+        eprintln!("Unable to set {}: {}", files[shown], e);
+    }
+    let mut paused = false;
 
-            match result {
-                Ok(s) => s,
-                Err(e) => {
-                    eprintln!("{}", e);
-                    process::exit(1);
+    let mut scheduler = Scheduler::new(SystemClock::default(), Interval::Times(times.clone()));
+    scheduler.listen(commands);
+    loop {
+        control::publish(Status {
+            wallpaper: files[shown].to_owned(),
+            next_change: Some(next_change(&times, scheduler.clock().now())),
+            paused,
+        });
+        match scheduler.wait() {
+            Wake::ClockJump => println!("Clock change detected, updating wallpaper"),
+            //sunrise and sunset move every day, so the schedule is worked out again
+            Wake::NewDay => {
+                let today = scheduler.clock().now().date();
+                match sun_schedule(&dir, &sun_args, today) {
+                    Ok(Some((new_times, new_files))) => {
+                        times = new_times;
+                        files = new_files;
+                        scheduler.set_times(times.clone());
+                    }
+                    Ok(None) => {}
+                    //yesterday's schedule is close enough to carry on with
+                    Err(e) => eprintln!("{}", e),
                 }
             }
-        };
-        sched_addto.run(sched_closure);
-    } else {
-        sched_addto = scheduler.every(days_val.day()).at("00:00");
-        let curr_fp = file_data_load("visited_days")?.into_iter().last().unwrap();
-        set_wallpaper(&curr_fp, Arc::clone(&progs), Arc::clone(&backend))?;
-        file_data_save(curr_fp.as_str(), "curr").unwrap();
-
-        let sched_closure = move || {
-            // append new chosen file name to the file
-            // setting function will look at file name at bottom
-            // and set accordingly.
-            let filepath_set = update_wallpaper_days(&dir);
-            let filepath_set = match filepath_set {
-                Ok(s) => s,
-                Err(e) => {
-                    eprintln!("{}", e);
-                    process::exit(1);
+            Wake::Scheduled => {}
+            Wake::Control(request) => {
+                match new_options(&request.command, &cli) {
+                    Some(Ok(new_setup)) => {
+                        let _ = request
+                            .reply
+                            .send(Ok(String::from("Now using the new options")));
+                        return Ok((new_setup, scheduler.stop_listening()));
+                    }
+                    //the old options are kept if the new ones don't work
+                    Some(Err(e)) => {
+                        eprintln!("Unable to switch to the new options: {}", e);
+                        let _ = request.reply.send(Err(e.to_string()));
+                        continue;
+                    }
+                    None => {}
                 }
-            };
-            set_wallpaper(&filepath_set, Arc::clone(&progs), Arc::clone(&backend)).unwrap();
-            file_data_save(&filepath_set, "curr").unwrap();
+                let now = scheduler.clock().now();
+                let result = match request.command {
+                    ControlCommand::Next | ControlCommand::Prev => {
+                        shown = if request.command == ControlCommand::Next {
+                            (shown + 1) % files.len()
+                        } else {
+                            (shown + files.len() - 1) % files.len()
+                        };
+                        let change = scheduled_change(
+                            &files,
+                            &times,
+                            shown,
+                            now,
+                            &sun_args,
+                            &options.monitor,
+                        );
+                        change_wallpaper(
+                            &change,
+                            Arc::clone(&progs),
+                            Arc::clone(&backend),
+                            &options,
+                        )
+                        .map(|_| format!("Set {}", files[shown]))
+                    }
+                    ControlCommand::Pause => {
+                        paused = true;
+                        Ok(String::from("Paused"))
+                    }
+                    ControlCommand::Resume => {
+                        paused = false;
+                        shown = current_index(&times, minute_of_day(now));
+                        let change = scheduled_change(
+                            &files,
+                            &times,
+                            shown,
+                            now,
+                            &sun_args,
+                            &options.monitor,
+                        );
+                        change_wallpaper(
+                            &change,
+                            Arc::clone(&progs),
+                            Arc::clone(&backend),
+                            &options,
+                        )
+                        .map(|_| String::from("Resumed"))
+                    }
+                    ControlCommand::Status => Ok(control::status().to_string()),
+                    //handled above
+                    ControlCommand::Reload | ControlCommand::Args { .. } => continue,
+                };
+                let _ = request.reply.send(result.map_err(|e| e.to_string()));
+                continue;
+            }
+        }
+
+        if paused {
+            continue;
+        }
+        let now = scheduler.clock().now();
+        //images deleted since the schedule was worked out are skipped until it's worked out again
+        shown = match next_available(&files, current_index(&times, minute_of_day(now))) {
+            Some(index) => index,
+            None => {
+                eprintln!("None of the images in {} exist any more", dir);
+                continue;
+            }
         };
-        sched_addto.run(sched_closure);
+        let change = scheduled_change(&files, &times, shown, now, &sun_args, &options.monitor);
+        //the next change may well work, so a failed one doesn't stop the schedule
+        if let Err(e) = set_wallpaper_retrying(&change, &progs, &backend, &options, max_failures) {
+            eprintln!("Unable to set {}: {}", files[shown], e);
+        }
+    }
+}
+
+//the index of the first image from `index` onwards, going round to the start, that still exists
+fn next_available(files: &[String], index: usize) -> Option<usize> {
+    (0..files.len())
+        .map(|offset| (index + offset) % files.len())
+        .find(|index| Path::new(&files[*index]).is_file())
+}
+
+//the options for the backend, taken from the ones for the listener
+fn set_options(args: &Args) -> SetOptions {
+    SetOptions {
+        monitor: args.monitor.to_owned(),
+        transition: args.transition.to_owned(),
+        transition_duration: args.transition_duration,
+        mode: args.mode,
+        modes: args.modes.clone().unwrap_or_default(),
+        dark_night: args.dark_night.unwrap_or_default(),
+    }
+}
+
+//what the programs are told when the image at `shown` in the schedule is set at `now`
+fn scheduled_change(
+    files: &[String],
+    times: &[Time],
+    shown: usize,
+    now: NaiveDateTime,
+    sun_args: &Args,
+    monitor: &Option<String>,
+) -> Change {
+    Change {
+        wallpaper: files[shown].to_owned(),
+        prev: Some(files[(shown + files.len() - 1) % files.len()].to_owned()),
+        next: Some(files[(shown + 1) % files.len()].to_owned()),
+        next_time: Some(next_change(times, now)),
+        index: shown + 1,
+        count: files.len(),
+        phase: phase_of(&files[shown], sun_args, now),
+        monitor: monitor.to_owned(),
+    }
+}
+
+//the phase of the day `filepath` is shown in when syncing to the sun, see `Change::phase`
+fn phase_of(filepath: &str, sun_args: &Args, now: NaiveDateTime) -> Option<String> {
+    let (lat, long) = (sun_args.lat?, sun_args.long?);
+    if !sun_args.solar {
+        let phase = Path::new(filepath).parent()?.file_name()?;
+        return phase.to_str().map(String::from);
     }
+    let now = Local.from_local_datetime(&now).earliest()?;
+    let (elevation, _) = solar::position(now.with_timezone(&Utc), lat, long);
+    let phase = if elevation > solar::horizon_angle(sun_args.elevation.unwrap_or(0.0)) {
+        PHASES[DAY_PHASE]
+    } else {
+        PHASES[NIGHT_PHASE]
+    };
+    Some(phase.to_string())
+}
 
+fn days_listener(setup: Setup, commands: Receiver<Request>) -> Result<Restart, Box<dyn Error>> {
+    let Setup { dir, args, cli, .. } = setup;
+    let options = set_options(&args);
+    let progs = Arc::new(args.programs);
+    let backend = Arc::new(args.backend);
+    let max_failures = args.max_failures.unwrap_or(DEFAULT_MAX_FAILURES);
+    let mut scheduler = Scheduler::new(
+        SystemClock::default(),
+        Interval::Days(args.days.unwrap_or(1)),
+    );
+
+    let mut curr_fp = file_data_load("visited_days")?.into_iter().last().unwrap();
+    let change = days_change(&dir, &curr_fp, scheduler.next_run(), &options.monitor);
+    //the desktop may never get ready, but the next change is still worth trying
+    match set_wallpaper_retrying(&change, &progs, &backend, &options, max_failures) {
+        Ok(()) => file_data_save(curr_fp.as_str(), "curr")?,
+        Err(e) => eprintln!("Unable to set {}: {}", curr_fp, e),
+    }
+    let mut paused = false;
+
+    scheduler.listen(commands);
     loop {
-        scheduler.run_pending();
-        sleep(Duration::from_millis(1000));
+        control::publish(Status {
+            wallpaper: curr_fp.to_owned(),
+            next_change: Some(scheduler.next_run()),
+            paused,
+        });
+        match scheduler.wait() {
+            Wake::Control(request) => {
+                match new_options(&request.command, &cli) {
+                    Some(Ok(new_setup)) => {
+                        let _ = request
+                            .reply
+                            .send(Ok(String::from("Now using the new options")));
+                        return Ok((new_setup, scheduler.stop_listening()));
+                    }
+                    //the old options are kept if the new ones don't work
+                    Some(Err(e)) => {
+                        eprintln!("Unable to switch to the new options: {}", e);
+                        let _ = request.reply.send(Err(e.to_string()));
+                        continue;
+                    }
+                    None => {}
+                }
+                let result = match request.command {
+                    ControlCommand::Next => update_wallpaper_days(&dir).and_then(|filepath| {
+                        let change =
+                            days_change(&dir, &filepath, scheduler.next_run(), &options.monitor);
+                        change_wallpaper(
+                            &change,
+                            Arc::clone(&progs),
+                            Arc::clone(&backend),
+                            &options,
+                        )?;
+                        file_data_save(&filepath, "curr")?;
+                        curr_fp = filepath;
+                        Ok(format!("Set {}", curr_fp))
+                    }),
+                    //goes back through the images already shown, without forgetting the ones
+                    //after it
+                    ControlCommand::Prev => file_data_load("visited_days").and_then(|visited| {
+                        let index = visited.iter().position(|file| *file == curr_fp);
+                        match index.and_then(|index| index.checked_sub(1)) {
+                            Some(index) => {
+                                curr_fp = visited[index].to_owned();
+                                let change = days_change(
+                                    &dir,
+                                    &curr_fp,
+                                    scheduler.next_run(),
+                                    &options.monitor,
+                                );
+                                change_wallpaper(
+                                    &change,
+                                    Arc::clone(&progs),
+                                    Arc::clone(&backend),
+                                    &options,
+                                )?;
+                                file_data_save(&curr_fp, "curr")?;
+                                Ok(format!("Set {}", curr_fp))
+                            }
+                            None => Ok(String::from("There is no previous wallpaper")),
+                        }
+                    }),
+                    ControlCommand::Pause => {
+                        paused = true;
+                        Ok(String::from("Paused"))
+                    }
+                    ControlCommand::Resume => {
+                        paused = false;
+                        Ok(String::from("Resumed"))
+                    }
+                    ControlCommand::Status => Ok(control::status().to_string()),
+                    //handled above
+                    ControlCommand::Reload | ControlCommand::Args { .. } => continue,
+                };
+                let _ = request.reply.send(result.map_err(|e| e.to_string()));
+                continue;
+            }
+            Wake::ClockJump => continue,
+            _ if paused => continue,
+            _ => {}
+        }
+
+        // append new chosen file name to the file
+        // setting function will look at file name at bottom
+        // and set accordingly.
+        let result = update_wallpaper_days(&dir).and_then(|filepath_set| {
+            let change = days_change(&dir, &filepath_set, scheduler.next_run(), &options.monitor);
+            set_wallpaper_retrying(&change, &progs, &backend, &options, max_failures)?;
+            file_data_save(&filepath_set, "curr")?;
+            curr_fp = filepath_set;
+            Ok(())
+        });
+        //the next change may well work, so a failed one doesn't stop the schedule
+        if let Err(e) = result {
+            eprintln!("Unable to change the wallpaper: {}", e);
+        }
+    }
+}
+
+//what the programs are told when `filepath` is set while changing every few days. The next image
+//is picked at random, so it isn't known yet
+fn days_change(
+    dir: &str,
+    filepath: &str,
+    next_time: NaiveDateTime,
+    monitor: &Option<String>,
+) -> Change {
+    let files = schedule_files(dir, 1).unwrap_or_default();
+    let visited = file_data_load("visited_days").unwrap_or_default();
+    let prev = visited
+        .iter()
+        .position(|file| file == filepath)
+        .and_then(|index| index.checked_sub(1))
+        .map(|index| visited[index].to_owned());
+    Change {
+        wallpaper: filepath.to_string(),
+        prev,
+        next: None,
+        next_time: Some(next_time),
+        index: files
+            .iter()
+            .position(|file| file == filepath)
+            .map_or(0, |index| index + 1),
+        count: files.len(),
+        phase: None,
+        monitor: monitor.to_owned(),
     }
 }
 
 fn commands_vec_loader(
-    filepath_set: &str,
+    change: &Change,
     progs: Arc<Option<Vec<String>>>,
     commands_vec: &mut Vec<Command>,
-) {
+) -> Result<(), Errors> {
     if let Some(prog_vec) = progs.as_deref() {
         for prog_str in prog_vec.iter() {
-            let mut wall_sent = false;
-            let mut prog_split = prog_str.split_whitespace();
-            let mut curr_command = Command::new(prog_split.next().unwrap());
-            for word in prog_split {
-                //replacing !WALL with the filepath
-                if word == "!WALL" {
-                    curr_command.arg(filepath_set);
-                    wall_sent = true;
-                } else {
-                    curr_command.arg(word);
-                }
-            }
+            //replacing !WALL (and the other placeholders) with the values of this change
+            let mut curr_command = hooks::prepare(prog_str, change)?;
             //if the filepath has been placed previously, this ensures that we dont place it again at the end
-            if wall_sent == false {
-                curr_command.arg(filepath_set);
+            if !prog_str.contains("!WALL") {
+                curr_command.arg(&change.wallpaper);
             }
             commands_vec.push(curr_command);
         }
     }
+    Ok(())
 }
 
 pub fn auto_time_setup(dir: &str) -> (Result<Time, Errors>, Time) {
-    let dir_count = WalkDir::new(dir).into_iter().count() - 1;
+    let dir_count = image_count(dir, 1);
     let step_time = if dir_count == 0 {
         Err(Errors::NoFilesFoundError(dir.to_string()))
     } else {
@@ -261,19 +697,28 @@ pub fn auto_time_setup(dir: &str) -> (Result<Time, Errors>, Time) {
 }
 
 pub fn print_schedule(dir: &str, min_depth: usize, args: Args) -> Result<(), Box<dyn Error>> {
-    let mut dir_iter = sorted_dir_iter(dir, min_depth);
-    let dir_count = sorted_dir_iter(dir, min_depth).count();
+    let files = match args.files {
+        Some(files) => files,
+        None => schedule_files(dir, min_depth)?,
+    };
+    let mut dir_iter = files.iter();
+    let dir_count = files.len();
     let mut sched_str: Vec<String> = vec![];
     let times = args.times.unwrap();
-    let mut times_iter = times.iter();
 
-    error_checking(&times, times_iter.next(), dir_count, args.days)?;
+    error_checking(&times, times.first(), dir_count, args.days)?;
 
-    for time in times_iter {
+    if let Some(date) = args.date {
+        println!("Schedule for {}", date);
+    }
+
+    for time in times.iter() {
         let file = dir_iter
             .next()
-            .ok_or(Errors::ConfigFileError(ConfigFileErrors::FileTimeMismatch))??;
-        let file = file.file_name();
+            .ok_or(Errors::ConfigFileError(ConfigFileErrors::FileTimeMismatch))?;
+        //showing the phase directory too when syncing to the sun, since names repeat across them
+        let file = Path::new(file);
+        let file = file.strip_prefix(dir).unwrap_or(file);
         sched_str.push(format!("Image: {:?} Time: {}", file, time.twelve_hour()));
     }
 
@@ -284,7 +729,12 @@ pub fn print_schedule(dir: &str, min_depth: usize, args: Args) -> Result<(), Box
     Ok(())
 }
 
-pub fn sorted_dir_iter(dir: &str, min_depth: usize) -> walkdir::IntoIter {
+///Returns the images `min_depth` levels into `dir`, in numerical order. Anything that isn't an
+///image is skipped, see `images::is_image`
+pub fn sorted_dir_iter(
+    dir: &str,
+    min_depth: usize,
+) -> impl Iterator<Item = walkdir::Result<DirEntry>> {
     WalkDir::new(dir)
         .sort_by(|a, b| {
             alphanumeric_sort::compare_str(
@@ -293,12 +743,57 @@ pub fn sorted_dir_iter(dir: &str, min_depth: usize) -> walkdir::IntoIter {
             )
         })
         .min_depth(min_depth)
+        .max_depth(min_depth)
         .into_iter()
+        .filter(|entry| {
+            entry
+                .as_ref()
+                .map_or(true, |entry| images::is_image(entry.path()))
+        })
+}
+
+///Returns the number of images `min_depth` levels into `dir`
+pub fn image_count(dir: &str, min_depth: usize) -> usize {
+    sorted_dir_iter(dir, min_depth).count()
+}
+
+///Returns the paths of the images to cycle through, in the order they are matched up with the
+///times. When syncing to the sun (`min_depth` of 2), the phase directories are listed in the
+///order they happen throughout the day rather than alphabetically
+pub fn schedule_files(dir: &str, min_depth: usize) -> Result<Vec<String>, Errors> {
+    if min_depth != 2 {
+        return dir_entry_paths(sorted_dir_iter(dir, min_depth));
+    }
+
+    let mut files = vec![];
+    for phase in PHASES.iter() {
+        let phase_dir = format!("{}/{}", dir, phase);
+        if Path::new(&phase_dir).is_dir() {
+            files.extend(phase_files(&phase_dir)?);
+        }
+    }
+    Ok(files)
+}
+
+fn phase_files(phase_dir: &str) -> Result<Vec<String>, Errors> {
+    dir_entry_paths(sorted_dir_iter(phase_dir, 1))
+}
+
+fn dir_entry_paths(
+    dir_iter: impl Iterator<Item = walkdir::Result<DirEntry>>,
+) -> Result<Vec<String>, Errors> {
+    dir_iter
+        .map(|file| {
+            let file = file.map_err(|_| Errors::FilePathError)?;
+            let filepath = file.path().to_str().ok_or(Errors::FilePathError)?;
+            Ok(filepath.to_string())
+        })
+        .collect()
 }
 
 pub fn shuffled_dir_vec(dir: &str, min_depth: usize) -> Vec<Result<DirEntry, walkdir::Error>> {
     let mut rng = thread_rng();
-    let mut dir_vector: Vec<_> = WalkDir::new(dir).min_depth(min_depth).into_iter().collect();
+    let mut dir_vector: Vec<_> = sorted_dir_iter(dir, min_depth).collect();
     dir_vector.shuffle(&mut rng);
     dir_vector
 }
@@ -359,273 +854,230 @@ fn error_checking(
     Ok(*loop_time)
 }
 
-#[cfg(windows)]
 fn de_command_spawn(
     filepath_set: &str,
     backend: Arc<Option<String>>,
+    options: &SetOptions,
 ) -> Result<(), Box<dyn Error>> {
-    if backend.is_some() {
-        eprintln!("NOTE: You are unable to select a backend on windows");
-    }
-    unsafe {
-        let file = OsStr::new(filepath_set)
-            .encode_wide()
-            // append null byte
-            .chain(iter::once(0))
-            .collect::<Vec<u16>>();
-        let successful = SystemParametersInfoW(
-            SPI_SETDESKWALLPAPER,
-            0,
-            file.as_ptr() as *mut c_void,
-            SPIF_UPDATEINIFILE | SPIF_SENDCHANGE,
-        ) == 1;
-
-        if successful {
-            println!("{} has been set as your wallpaper", filepath_set);
-            Ok(())
-        } else {
-            Err(io::Error::last_os_error().into())
-        }
-    }
+    let backend = backend::resolve(backend.as_deref());
+    backend.set(filepath_set, &options.for_image(filepath_set))?;
+
+    println!("{} has been set as your wallpaper", filepath_set);
+    Ok(())
 }
 
-#[cfg(not(windows))]
-fn de_command_spawn(
-    filepath_set: &str,
-    backend: Arc<Option<String>>,
-) -> Result<(), Box<dyn Error>> {
-    let backend = backend.as_deref();
-    let gnome = vec![
-        UniCase::new("gnome"),
-        UniCase::new("gnome-xorg"),
-        UniCase::new("ubuntu"),
-        UniCase::new("deepin"),
-        UniCase::new("pop"),
-        UniCase::new("ubuntu:gnome"),
-    ];
-    let pantheon = UniCase::new("pantheon");
-    let mate = UniCase::new("mate");
-    let kde = vec![
-        UniCase::new("plasma"),
-        UniCase::new("neon"),
-        UniCase::new("kde"),
-        UniCase::new("/usr/share/xsessions/plasma"),
-    ];
-    let lxde = UniCase::new("lxde");
-    let xfce = vec![
-        UniCase::new("xfce"),
-        UniCase::new("xubuntu"),
-        UniCase::new("xfce session"),
-    ];
-
-    let curr_de = env::var("XDG_CURRENT_DESKTOP");
-    let curr_de = match curr_de {
-        Err(_) => String::from("Other"),
-        Ok(de) => de,
-    };
-    let mut curr_de = UniCase::new(curr_de.as_str());
-
-    let mut feh_handle = Command::new("feh");
-    let feh_handle = feh_handle.arg("--bg-scale").arg(filepath_set);
-
-    //Gnome, Ubuntu, Deepin, Pop
-    let mut gnome_handle = Command::new("gsettings");
-    let gnome_handle = gnome_handle
-        .arg("set")
-        .arg("org.gnome.desktop.background")
-        .arg("picture-uri")
-        .arg(format!("'file://{}'", filepath_set));
-
-    //Pantheon
-    let mut multiarch_dir = String::from("/usr/lib/");
-    multiarch_dir.push_str(ARCH);
-    multiarch_dir.push_str("-linux-gnu/");
-    let mut pantheon_handle = Command::new(multiarch_dir + "io.elementary.contract.set-wallpaper");
-    let pantheon_handle = pantheon_handle.arg(format!("{}", filepath_set));
-
-    //kde
-    let kde_script_beg = r#"
-qdbus org.kde.plasmashell /PlasmaShell org.kde.PlasmaShell.evaluateScript "
-    var allDesktops = desktops();
-    print (allDesktops);
-    for (i=0;i<allDesktops.length;i++) {
-        d = allDesktops[i];
-        d.wallpaperPlugin = 'org.kde.image';
-        d.currentConfigGroup = Array('Wallpaper',
-                                    'org.kde.image',
-                                    'General');
-        d.writeConfig('Image', 'file://"#;
-    let kde_script_end = r#"')
-        }""#;
-    let kde_script = format!("{}{}{}", kde_script_beg, filepath_set, kde_script_end);
-
-    //lxde
-    let mut lxde_handle = Command::new("pcmanfm");
-    let lxde_handle = lxde_handle.arg("--set-wallpaper").arg(filepath_set);
-
-    //mate
-    let mut mate_handle = Command::new("gsettings set org.mate.background picture-filename");
-    let mate_handle = mate_handle
-        .arg("set")
-        .arg("org.mate.background")
-        .arg("picture-uri")
-        .arg(format!("'file://{}'", filepath_set));
-
-    //let xfce_script_beg = r#"xfconf-query -c xfce4-desktop \
-    //-p /backdrop/screen0/monitor0/workspace0/last-image \
-    //-s ""#;
-    let xfce_script_beg = "xfconf-query -c xfce4-desktop -l | grep last-image | while read path; do xfconf-query -c xfce4-desktop -p $path -s ";
-    let xfce_script_end = r#"; done"#;
-    let xfce_script = format!("{}{}{}", xfce_script_beg, filepath_set, xfce_script_end);
-
-    //to avoid uninitialized variable error
-    //safe because its not used unless custom backend specified
-    let mut backend_split: Vec<&str> = vec![];
-
-    let mut cust_backend = false;
-    if let Some(back) = backend {
-        curr_de = UniCase::new(back);
-        cust_backend = true;
-        for word in back.split_whitespace() {
-            backend_split.push(word);
-        }
+///The times the wallpaper changes at, alongside the images matched up with them
+pub type SunSchedule = (Vec<Time>, Vec<String>);
+
+///Divides the images of each phase of the day between the times the sun passes through that
+///phase on the given date. Only the day and night directories are required, any other phase
+///that has no directory is taken over by day or night. When the sun doesn't rise or set at all
+///(polar night and polar day), only the night or day images are used, spread over the whole day
+pub fn sun_timings(
+    dir: &str,
+    lat: f64,
+    long: f64,
+    elevation: f64,
+    date: NaiveDate,
+    twilight: Twilight,
+) -> Result<SunSchedule, Box<dyn Error>> {
+    if !(-90.0..=90.0).contains(&lat) {
+        return Err(Errors::SunPositionError(format!(
+            "latitude {} is not between -90 and 90",
+            lat
+        ))
+        .into());
+    } else if !(-180.0..=180.0).contains(&long) {
+        return Err(Errors::SunPositionError(format!(
+            "longitude {} is not between -180 and 180",
+            long
+        ))
+        .into());
     }
 
-    if gnome.contains(&curr_de) {
-        gnome_handle
-            .spawn()
-            .map_err(|_| Errors::ProgramRunError(String::from("Gnome Wallpaper Adjuster")))?;
-    } else if lxde == curr_de {
-        lxde_handle
-            .spawn()
-            .map_err(|_| Errors::ProgramRunError(String::from("LXDE Wallpaper Adjuster")))?;
-    } else if pantheon == curr_de {
-        pantheon_handle
-            .spawn()
-            .map_err(|_| Errors::ProgramRunError(String::from("Pantheon Wallpaper Adjuster")))?;
-    } else if mate == curr_de {
-        mate_handle
-            .spawn()
-            .map_err(|_| Errors::ProgramRunError(String::from("Mate Wallpaper Adjuster")))?;
-    } else if kde.contains(&curr_de) {
-        run_script::run(kde_script.as_str(), &vec![], &ScriptOptions::new())
-            .map_err(|_| Errors::ProgramRunError(String::from("KDE Wallpaper Adjuster")))?;
-    } else if xfce.contains(&curr_de) {
-        run_script::run(xfce_script.as_str(), &vec![], &ScriptOptions::new())
-            .map_err(|_| Errors::ProgramRunError(String::from("XFCE Wallpaper Adjuster")))?;
-    } else if !cust_backend || curr_de == UniCase::new("feh") {
-        feh_handle
-            .spawn()
-            .map_err(|_| Errors::ProgramRunError(String::from("Feh")))?;
-    } else if cust_backend {
-        let mut backend_split = backend_split.into_iter();
-        let mut cust_handle = Command::new(backend_split.next().unwrap());
-        let mut wall_sent = false;
-        for word in backend_split {
-            if word == "!WALL" {
-                wall_sent = true;
-                cust_handle.arg(filepath_set);
-            } else {
-                cust_handle.arg(word);
+    let dir_counts = sun_timings_dir_counts(dir)?;
+
+    let local_time = |angle: f64, direction: Direction| {
+        solar::time_at_elevation(date, lat, long, angle, direction)
+            .time()
+            .map(|time| {
+                let time = time.with_timezone(&Local);
+                Time::new((time.hour() * 60) + time.minute())
+            })
+    };
+    let horizon = solar::horizon_angle(elevation);
+
+    //the phases used today, with the time they start at
+    let phases: Vec<(usize, Time)> =
+        match solar::time_at_elevation(date, lat, long, horizon, Direction::Rising) {
+            Crossing::AlwaysAbove => vec![(DAY_PHASE, Time::default())],
+            Crossing::AlwaysBelow => vec![(NIGHT_PHASE, Time::default())],
+            Crossing::At(_) => {
+                //both exist since the sun crosses the horizon
+                let sunrise = local_time(horizon, Direction::Rising).unwrap_or_default();
+                let sunset = local_time(horizon, Direction::Setting).unwrap_or_default();
+
+                //the golden hours and twilight only happen if the sun gets high or low enough
+                let golden = local_time(GOLDEN_HOUR_ANGLE, Direction::Rising)
+                    .zip(local_time(GOLDEN_HOUR_ANGLE, Direction::Setting));
+                let twilight = local_time(twilight.angle(), Direction::Rising)
+                    .zip(local_time(twilight.angle(), Direction::Setting));
+
+                //the time each phase starts at, in the same order as PHASES, and whether the
+                //phase is taken over by the phase after it (rather than the one before it) when
+                //it has no images
+                let starts = [
+                    (golden.map(|_| sunrise), true),
+                    (Some(golden.map_or(sunrise, |(rise, _)| rise)), false),
+                    (golden.map(|(_, set)| set), false),
+                    (twilight.map(|_| sunset), true),
+                    (Some(twilight.map_or(sunset, |(_, set)| set)), false),
+                    (twilight.map(|(rise, _)| rise), false),
+                ];
+
+                //phases that can't happen today are treated the same as phases without images
+                let mut phases = vec![];
+                let mut pending_start = None;
+                for (phase, (start, to_next)) in starts.iter().enumerate() {
+                    let required = phase == DAY_PHASE || phase == NIGHT_PHASE;
+                    match start {
+                        Some(start) if dir_counts[phase] > 0 || required => {
+                            phases.push((phase, pending_start.take().unwrap_or(*start)));
+                        }
+                        Some(start) if *to_next => pending_start = Some(*start),
+                        _ => {}
+                    }
+                }
+                phases
             }
-        }
+        };
 
-        if !wall_sent {
-            cust_handle.arg(filepath_set);
+    let mut times: Vec<Time> = vec![];
+    let mut files: Vec<String> = vec![];
+    for (i, (phase, start)) in phases.iter().enumerate() {
+        let phase_dir = format!("{}/{}", dir, PHASES[*phase]);
+        let count = dir_counts[*phase];
+        if count == 0 {
+            return Err(Errors::NoFilesFoundError(phase_dir).into());
         }
 
-        cust_handle
-            .spawn()
-            .map_err(|_| Errors::ProgramRunError(curr_de.to_string()))?;
-    } else {
-        return Err(Errors::BackendNotFoundError(curr_de.to_string()).into());
+        let (_, end) = phases[(i + 1) % phases.len()];
+        let span = match (end - *start).total_mins {
+            //only one phase today, which lasts the whole day
+            0 => FULL_DAY.total_mins,
+            span => span,
+        };
+        let step_time = span / count;
+        for step in 0..count {
+            times.push(Time::new(
+                (start.total_mins + step * step_time) % FULL_DAY.total_mins,
+            ));
+        }
+        files.extend(phase_files(&phase_dir)?);
     }
-
-    println!("{} has been set as your wallpaper", filepath_set);
-    Ok(())
+    Ok((times, files))
 }
 
-pub fn sun_timings(
+///Matches the images with the elevation of the sun rather than with times. Each image is shown
+///while the sun is closer to its elevation than to any other image's, and the times this
+///happens at on the given date are worked out minute by minute. If `elevations` isn't given,
+///the images are spread between the lowest and highest points the sun reaches that day
+pub fn elevation_timings(
     dir: &str,
     lat: f64,
     long: f64,
-    elevation: f64,
-) -> Result<Vec<Time>, Box<dyn Error>> {
-    let dir_night = format!("{}/night", dir);
-    let dir_night = dir_night.as_str();
-    let dir_day = format!("{}/day", dir);
-    let dir_day = dir_day.as_str();
-    let (dir_count_day, dir_count_night) = sun_timings_dir_counts(dir, dir_day, dir_night)?;
-    if dir_count_day == 0 {
-        return Err(Errors::NoFilesFoundError(String::from(dir_day)).into());
-    } else if dir_count_night == 0 {
-        return Err(Errors::NoFilesFoundError(String::from(dir_night)).into());
+    elevations: Option<&[f64]>,
+    date: NaiveDate,
+) -> Result<SunSchedule, Box<dyn Error>> {
+    let dir_files = schedule_files(dir, 1)?;
+    if dir_files.is_empty() {
+        return Err(Errors::NoFilesFoundError(dir.to_string()).into());
     }
-    let mut times: Vec<Time> = vec![];
-    let (sunrise, sunset) = sun_times::sun_times(Utc::today(), lat, long, elevation);
-    let (sunset, sunrise) = (sunset.with_timezone(&Local), sunrise.with_timezone(&Local));
-    let sunset = Time::new((sunset.hour() * 60) + sunset.minute());
-    let sunrise = Time::new((sunrise.hour() * 60) + sunrise.minute());
-    let step_time_day = Time::new((sunset.total_mins - sunrise.total_mins) / (dir_count_day));
-    let step_time_night =
-        Time::new((1440 - (sunset.total_mins - sunrise.total_mins)) / dir_count_night);
-    let mut loop_time_night: Time;
-    let mut loop_time_day = sunrise.to_owned();
-
-    while loop_time_day <= sunset {
-        if loop_time_day >= FULL_DAY {
-            times.push(loop_time_day - FULL_DAY);
-        } else {
-            times.push(loop_time_day);
-        }
-        loop_time_day += step_time_day;
-    }
-    //poping off the last one (which will either be the sunset time, or be very close, and replace
-    //it with the sunset time
-    times.pop();
-    loop_time_night = sunset.to_owned();
 
-    while loop_time_night < (sunrise + FULL_DAY) {
-        if loop_time_night >= FULL_DAY {
-            times.push(loop_time_night - FULL_DAY);
-        } else {
-            times.push(loop_time_night);
+    let elevations = match elevations {
+        Some(elevations) if elevations.len() != dir_files.len() => {
+            return Err(Errors::ConfigFileError(ConfigFileErrors::FileElevationMismatch).into());
         }
-        loop_time_night += step_time_night;
-    }
+        Some(elevations) => elevations.to_vec(),
+        None => {
+            let (lowest, highest) = solar::elevation_range(date, lat, long);
+            solar::spread_elevations(dir_files.len(), lowest, highest)
+        }
+    };
 
-    //Because of the rounding that takes place when determining step time, it may throw off the
-    //timing, possibly making it so that an extra time slips through
-    //when this takes place, it would mean that the difference between the last time and the first
-    //time (sunrise) is less than step_time_night, so in this case we would simply pop off the
-    //extra time segment
-    if i32::abs(sunrise.total_mins as i32 - times[times.len() - 1].total_mins as i32)
-        < step_time_night.total_mins as i32
-    {
-        times.pop();
+    let midnight = date
+        .and_hms_opt(0, 0, 0)
+        .and_then(|midnight| Local.from_local_datetime(&midnight).earliest())
+        .ok_or_else(|| Errors::SunPositionError(format!("{} has no midnight", date)))?;
+
+    let mut times: Vec<Time> = vec![];
+    let mut files: Vec<String> = vec![];
+    let mut last_index = None;
+    for minute in 0..FULL_DAY.total_mins {
+        let at = midnight + Duration::minutes(minute as i64);
+        let (elevation, direction) = solar::position(at.with_timezone(&Utc), lat, long);
+        let index = solar::nearest_elevation(&elevations, elevation, direction);
+        if last_index != Some(index) {
+            times.push(Time::new(minute));
+            files.push(dir_files[index].to_string());
+            last_index = Some(index);
+        }
     }
-    Ok(times)
+    Ok((times, files))
 }
 
-fn sun_timings_dir_counts(
+///Works out the schedule for the given date when syncing to the sun, or returns None if the
+///location isn't set
+pub fn sun_schedule(
     dir: &str,
-    dir_day: &str,
-    dir_night: &str,
-) -> Result<(u32, u32), Box<dyn Error>> {
+    args: &Args,
+    date: NaiveDate,
+) -> Result<Option<SunSchedule>, Box<dyn Error>> {
+    let (lat, long) = match (args.lat, args.long) {
+        (Some(lat), Some(long)) => (lat, long),
+        _ => return Ok(None),
+    };
+
+    let schedule = if args.solar {
+        elevation_timings(dir, lat, long, args.elevations.as_deref(), date)?
+    } else {
+        let elevation = args.elevation.unwrap_or(0.0);
+        let twilight = args.twilight.unwrap_or_default();
+        sun_timings(dir, lat, long, elevation, date, twilight)?
+    };
+    Ok(Some(schedule))
+}
+
+//returns the number of images in each phase directory, in the same order as PHASES. Phases
+//without a directory have a count of 0
+fn sun_timings_dir_counts(dir: &str) -> Result<Vec<u32>, Box<dyn Error>> {
     //checking if the directories exist
     if check_dir_exists(dir).is_err() {
-        Err(Errors::FilePathError.into())
-    } else if check_dir_exists(dir_night).is_err() || check_dir_exists(dir_day).is_err() {
-        Err("Error: Make sure night and day directories are created within master directory".into())
-    } else {
-        //now we know directories exist, so lets get the counts of the night
-        //and day directories and send it to sun_timings function to get vector
-        //of times based on sunset and sunrise
-        let dir_count_night = WalkDir::new(dir_night).min_depth(1).into_iter().count();
-        let dir_count_day = WalkDir::new(dir_day).min_depth(1).into_iter().count();
-        Ok((dir_count_day as u32, dir_count_night as u32))
+        return Err(Errors::FilePathError.into());
+    }
+    if check_dir_exists(&format!("{}/night", dir)).is_err()
+        || check_dir_exists(&format!("{}/day", dir)).is_err()
+    {
+        return Err(
+            "Error: Make sure night and day directories are created within master directory".into(),
+        );
     }
+
+    //now we know directories exist, so lets get the counts of each of them and send it to
+    //sun_timings function to get vector of times based on the position of the sun
+    Ok(PHASES
+        .iter()
+        .map(|phase| {
+            let phase_dir = format!("{}/{}", dir, phase);
+            if Path::new(&phase_dir).is_dir() {
+                image_count(&phase_dir, 1) as u32
+            } else {
+                0
+            }
+        })
+        .collect())
 }
+
 pub fn check_dir_exists(dir: &str) -> Result<(), Errors> {
     let mut dir_iter = WalkDir::new(dir).into_iter();
 
@@ -660,7 +1112,7 @@ pub fn file_data_save(contents: &str, filename: &str) -> Result<(), Box<dyn Erro
     let data_dir = data_dir.to_str().unwrap();
     println!("{}", data_dir);
     let filepath = format!("{}/dyn-wall-rs/{}", data_dir, filename);
-    let mut data_file = OpenOptions::new().write(true).append(true).open(filepath)?;
+    let mut data_file = OpenOptions::new().append(true).open(filepath)?;
     let newlined = contents.to_string() + "\n";
 
     data_file.write_all(newlined.as_bytes())?;
@@ -705,32 +1157,117 @@ pub fn set_wallpaper(
     progs: Arc<Option<Vec<String>>>,
     backend: Arc<Option<String>>,
 ) -> Result<(), Box<dyn Error>> {
+    change_wallpaper(
+        &Change::new(filepath_set),
+        progs,
+        backend,
+        &SetOptions::default(),
+    )
+}
+
+///Sets the wallpaper like `set_wallpaper`, telling the programs the details of the change and
+///the backend how to set it
+pub fn change_wallpaper(
+    change: &Change,
+    progs: Arc<Option<Vec<String>>>,
+    backend: Arc<Option<String>>,
+    options: &SetOptions,
+) -> Result<(), Box<dyn Error>> {
+    change_wallpaper_with(change, progs, || {
+        de_command_spawn(&change.wallpaper, backend, options)
+    })
+}
+
+//sets the wallpaper through `set`, then runs the programs and hooks. Only the backend setting the
+//wallpaper can fail the change; a program failing is printed, the same as a hook failing
+fn change_wallpaper_with(
+    change: &Change,
+    progs: Arc<Option<Vec<String>>>,
+    set: impl FnOnce() -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let filepath_set = change.wallpaper.as_str();
     let mut commands_vec: Vec<Command> = vec![];
 
     //this is to send the file as an argument to the user specified program, if one was specified
-    commands_vec_loader(&filepath_set, Arc::clone(&progs), &mut commands_vec);
+    commands_vec_loader(change, Arc::clone(&progs), &mut commands_vec)?;
 
     //this is for the edge case where the current time is after the last time specified for the day, but before the first one specified for the day
     //in that case, the previous loop would push nothing to filepath_set, and so nothing would be sent to feh
     //what we want in this situation is for the file that is associated with the last time of the day to be sent as an argument to feh,
     //and to the user specified program
-    de_command_spawn(&filepath_set, backend)?;
+    set()?;
+    #[cfg(feature = "dbus")]
+    dbus::wallpaper_changed(filepath_set);
 
     if let Some(progs) = progs.as_deref() {
-        let mut prog_iter = progs.iter();
-        for curr_command in commands_vec.iter_mut() {
-            curr_command
-                .spawn()
-                .map_err(|_| Errors::ProgramRunError(String::from(prog_iter.next().unwrap())))?;
-            println!(
-                "The image {} has been sent as an argument to the specified program",
-                filepath_set
-            );
+        for (curr_command, prog) in commands_vec.iter_mut().zip(progs) {
+            match command::run(curr_command, prog) {
+                Ok(()) => println!(
+                    "The image {} has been sent as an argument to the specified program",
+                    filepath_set
+                ),
+                Err(e) => eprintln!("{}", e),
+            }
         }
     }
+    hooks::run(change);
     Ok(())
 }
 
+//sets the wallpaper, trying the backend again with a growing delay if it fails for a reason that
+//can go away by itself, like the desktop not being ready yet when logging in. Gives up after
+//`max_failures` attempts in a row, or straight away for anything else, like a problem with the
+//config
+fn set_wallpaper_retrying(
+    change: &Change,
+    progs: &Arc<Option<Vec<String>>>,
+    backend: &Arc<Option<String>>,
+    options: &SetOptions,
+    max_failures: u32,
+) -> Result<(), Box<dyn Error>> {
+    change_wallpaper_with(change, Arc::clone(progs), || {
+        retry(max_failures, thread::sleep, || {
+            de_command_spawn(&change.wallpaper, Arc::clone(backend), options)
+        })
+    })
+}
+
+//calls `attempt` until it works, it fails `max_failures` times in a row or it fails in a way that
+//isn't transient, waiting through `sleep` between attempts
+fn retry(
+    max_failures: u32,
+    mut sleep: impl FnMut(std::time::Duration),
+    mut attempt: impl FnMut() -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut delay = RETRY_DELAY;
+    let mut failures = 1;
+    loop {
+        match attempt() {
+            Ok(()) => return Ok(()),
+            Err(e) if is_transient(e.as_ref()) && failures < max_failures => {
+                eprintln!("{}, trying again in {} seconds", e, delay.as_secs());
+                sleep(delay);
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+                failures += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+///Returns true for errors that may not happen again if the same thing is tried later: a program
+///that couldn't be started, took too long, stopped straight after starting or exited with an
+///error, which is what gsettings and hyprctl do before the session is ready
+pub fn is_transient(error: &(dyn Error + 'static)) -> bool {
+    matches!(
+        error.downcast_ref::<Errors>(),
+        Some(Errors::ProgramRunError(_))
+            | Some(Errors::CommandTimeoutError(..))
+            | Some(Errors::CommandStartError(..))
+            | Some(Errors::CommandFailedError(..))
+    )
+}
+
 pub fn update_wallpaper_days(dir: &str) -> Result<String, Box<dyn Error>> {
     let dir_vector = shuffled_dir_vec(dir, 1);
     let dir_iter = dir_vector.into_iter();
@@ -759,6 +1296,445 @@ pub fn update_wallpaper_days(dir: &str) -> Result<String, Box<dyn Error>> {
     Ok(filepath_set)
 }
 
-pub fn get_curr_back() -> Result<String, Box<dyn Error>> {
-    Ok(file_data_load("curr")?.into_iter().last().unwrap())
+//the data file only knows about wallpapers this program set, so when there isn't one the backend
+//is asked for what it has set
+pub fn get_curr_back(backend: Option<&str>) -> Result<String, Box<dyn Error>> {
+    match file_data_load("curr")
+        .ok()
+        .and_then(|curr| curr.into_iter().last())
+        .filter(|wp| !wp.is_empty())
+    {
+        Some(wp) => Ok(wp),
+        None => backend::resolve(backend)
+            .current()
+            .ok_or_else(|| "unable to retrieve current wallpaper".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, env::temp_dir, path::PathBuf, time::Duration as StdDuration};
+
+    fn failed() -> Box<dyn Error> {
+        Errors::CommandFailedError("gsettings".to_string(), Some(1), String::new()).into()
+    }
+
+    #[test]
+    fn retry_backs_off_until_the_backend_works() {
+        let sleeps = RefCell::new(vec![]);
+        let mut attempts = 0;
+        let result = retry(
+            5,
+            |delay| sleeps.borrow_mut().push(delay),
+            || {
+                attempts += 1;
+                if attempts < 4 {
+                    Err(failed())
+                } else {
+                    Ok(())
+                }
+            },
+        );
+        assert!(result.is_ok());
+        assert_eq!(attempts, 4);
+        assert_eq!(
+            sleeps.into_inner(),
+            [1, 2, 4].map(StdDuration::from_secs).to_vec()
+        );
+    }
+
+    #[test]
+    fn retry_gives_up_after_max_failures_with_the_delay_capped() {
+        let sleeps = RefCell::new(vec![]);
+        let mut attempts = 0;
+        let result = retry(
+            10,
+            |delay| sleeps.borrow_mut().push(delay),
+            || {
+                attempts += 1;
+                Err(failed())
+            },
+        );
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<Errors>(),
+            Some(Errors::CommandFailedError(..))
+        ));
+        assert_eq!(attempts, 10);
+        assert_eq!(
+            sleeps.into_inner(),
+            [1, 2, 4, 8, 16, 32, 60, 60, 60]
+                .map(StdDuration::from_secs)
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn retry_stops_straight_away_on_other_errors() {
+        let mut attempts = 0;
+        let result = retry(
+            5,
+            |_| panic!("shouldn't wait"),
+            || {
+                attempts += 1;
+                Err(Errors::CommandParseError("feh '".to_string()).into())
+            },
+        );
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn programs_run_only_once_the_backend_has_set_the_wallpaper() {
+        let marker = temp_dir().join(format!("dyn-wall-rs-programs-{}", std::process::id()));
+        let _ = fs::remove_file(&marker);
+        let progs = Arc::new(Some(vec![format!("touch {}", marker.display())]));
+        let change = Change::new("/home/me/1.png");
+
+        let mut attempts = 0;
+        let result = change_wallpaper_with(&change, Arc::clone(&progs), || {
+            retry(
+                3,
+                |_| {},
+                || {
+                    attempts += 1;
+                    Err(failed())
+                },
+            )
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+        assert!(!marker.exists());
+
+        let mut attempts = 0;
+        let result = change_wallpaper_with(&change, progs, || {
+            retry(
+                3,
+                |_| {},
+                || {
+                    attempts += 1;
+                    if attempts < 2 {
+                        Err(failed())
+                    } else {
+                        Ok(())
+                    }
+                },
+            )
+        });
+        assert!(result.is_ok());
+        assert!(marker.exists());
+        let _ = fs::remove_file(&marker);
+    }
+
+    const LONDON: (f64, f64) = (51.5074, -0.1278);
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    //a wallpaper directory with the given number of images in each subdirectory
+    fn wallpapers(name: &str, dirs: &[(&str, usize)]) -> PathBuf {
+        let dir = temp_dir().join(format!("dyn-wall-rs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (subdir, count) in dirs.iter() {
+            fs::create_dir_all(dir.join(subdir)).unwrap();
+            for image in 1..=*count {
+                File::create(dir.join(subdir).join(format!("{}.png", image))).unwrap();
+            }
+        }
+        dir
+    }
+
+    //the local time the sun crosses `angle`, the way the schedule works it out
+    fn local_time(
+        day: NaiveDate,
+        (lat, long): (f64, f64),
+        angle: f64,
+        direction: Direction,
+    ) -> Time {
+        let time = solar::time_at_elevation(day, lat, long, angle, direction)
+            .time()
+            .unwrap()
+            .with_timezone(&Local);
+        Time::new(time.hour() * 60 + time.minute())
+    }
+
+    //the phase directory each image of the schedule is in
+    fn phases_of(files: &[String]) -> Vec<String> {
+        files
+            .iter()
+            .map(|file| {
+                Path::new(file)
+                    .parent()
+                    .and_then(Path::file_name)
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    }
+
+    fn timings(dir: &Path, place: (f64, f64), day: NaiveDate, twilight: Twilight) -> SunSchedule {
+        sun_timings(dir.to_str().unwrap(), place.0, place.1, 0.0, day, twilight).unwrap()
+    }
+
+    #[test]
+    fn every_phase_starts_when_the_sun_reaches_it() {
+        let day = date(2024, 3, 20);
+        let dir = wallpapers("phases", &PHASES.map(|phase| (phase, 1)));
+        let (times, files) = timings(&dir, LONDON, day, Twilight::Civil);
+        assert_eq!(phases_of(&files), PHASES);
+        let at = |angle, direction| local_time(day, LONDON, angle, direction);
+        let horizon = solar::horizon_angle(0.0);
+        assert_eq!(
+            times,
+            [
+                at(horizon, Direction::Rising),
+                at(GOLDEN_HOUR_ANGLE, Direction::Rising),
+                at(GOLDEN_HOUR_ANGLE, Direction::Setting),
+                at(horizon, Direction::Setting),
+                at(Twilight::Civil.angle(), Direction::Setting),
+                at(Twilight::Civil.angle(), Direction::Rising),
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_phase_directories_are_covered_by_day_and_night() {
+        let day = date(2024, 3, 20);
+        let horizon = solar::horizon_angle(0.0);
+        let sunrise = local_time(day, LONDON, horizon, Direction::Rising);
+        let sunset = local_time(day, LONDON, horizon, Direction::Setting);
+
+        //day runs from sunrise to sunset, and night the rest of the day
+        let dir = wallpapers("day-night", &[("day", 2), ("night", 2)]);
+        let (times, files) = timings(&dir, LONDON, day, Twilight::Civil);
+        assert_eq!(phases_of(&files), ["day", "day", "night", "night"]);
+        let day_step = (sunset - sunrise).total_mins / 2;
+        let night_step = (sunrise - sunset).total_mins / 2;
+        assert_eq!(
+            times,
+            [
+                sunrise,
+                Time::new(sunrise.total_mins + day_step),
+                sunset,
+                Time::new((sunset.total_mins + night_step) % FULL_DAY.total_mins),
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+
+        //dusk takes over from day at sunset, and night carries on through dawn
+        let dir = wallpapers("dusk", &[("day", 1), ("dusk", 1), ("night", 1)]);
+        let (times, files) = timings(&dir, LONDON, day, Twilight::Civil);
+        assert_eq!(phases_of(&files), ["day", "dusk", "night"]);
+        assert_eq!(
+            times,
+            [
+                sunrise,
+                sunset,
+                local_time(day, LONDON, Twilight::Civil.angle(), Direction::Setting),
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+
+        let dir = wallpapers("no-night", &[("day", 1)]);
+        assert!(sun_timings(dir.to_str().unwrap(), 51.5, 0.0, 0.0, day, Twilight::Civil).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn phases_that_do_not_happen_are_skipped() {
+        let horizon = solar::horizon_angle(0.0);
+        let dir = wallpapers("skipped", &PHASES.map(|phase| (phase, 1)));
+
+        //it never gets dark enough for astronomical twilight in a london summer, so night starts
+        //at sunset and lasts until sunrise
+        let day = date(2024, 6, 21);
+        let (times, files) = timings(&dir, LONDON, day, Twilight::Astronomical);
+        assert_eq!(
+            phases_of(&files),
+            ["golden_morning", "day", "golden_evening", "night"]
+        );
+        assert_eq!(
+            times[3],
+            local_time(day, LONDON, horizon, Direction::Setting)
+        );
+
+        //in the north of finland in winter, the sun rises without getting high enough for the
+        //golden hours to end
+        let oulu = (65.0121, 25.4651);
+        let day = date(2024, 12, 21);
+        let (times, files) = timings(&dir, oulu, day, Twilight::Civil);
+        assert_eq!(phases_of(&files), ["day", "dusk", "night", "dawn"]);
+        assert_eq!(times[0], local_time(day, oulu, horizon, Direction::Rising));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn polar_day_and_night_spread_one_phase_over_the_day() {
+        let tromso = (69.6492, 18.9553);
+        let dir = wallpapers("polar", &[("day", 3), ("night", 2), ("dusk", 1)]);
+
+        let (times, files) = timings(&dir, tromso, date(2024, 6, 21), Twilight::Civil);
+        assert_eq!(phases_of(&files), ["day", "day", "day"]);
+        assert_eq!(times, [Time::new(0), Time::new(480), Time::new(960)]);
+
+        let (times, files) = timings(&dir, tromso, date(2024, 12, 21), Twilight::Civil);
+        assert_eq!(phases_of(&files), ["night", "night"]);
+        assert_eq!(times, [Time::new(0), Time::new(720)]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn schedule_is_worked_out_for_the_date_asked_for() {
+        let dir = wallpapers("dates", &[("day", 1), ("night", 1)]);
+        let args = Args {
+            lat: Some(LONDON.0),
+            long: Some(LONDON.1),
+            ..Args::default()
+        };
+        let schedule = |day| {
+            sun_schedule(dir.to_str().unwrap(), &args, day)
+                .unwrap()
+                .unwrap()
+                .0
+        };
+        let horizon = solar::horizon_angle(0.0);
+        let sun = |day| {
+            [
+                local_time(day, LONDON, horizon, Direction::Rising),
+                local_time(day, LONDON, horizon, Direction::Setting),
+            ]
+        };
+
+        let (winter, summer) = (date(2024, 12, 21), date(2024, 6, 21));
+        assert_eq!(schedule(winter), sun(winter));
+        assert_eq!(schedule(summer), sun(summer));
+        //the sun rises hours earlier in summer, so these can't both be today's
+        assert!(schedule(summer)[0].total_mins + 180 < schedule(winter)[0].total_mins);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_second_instance_cannot_take_the_lock() {
+        let path = temp_dir().join(format!("dyn-wall-rs-lock-{}", process::id()));
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+
+        let held = lock(path).unwrap();
+        let second = lock(path).unwrap_err();
+        assert!(matches!(
+            second.downcast_ref::<Errors>(),
+            Some(Errors::AlreadyRunningError(Some(pid))) if *pid == process::id().to_string()
+        ));
+
+        //it can be taken again once the first instance is gone
+        drop(held);
+        assert!(lock(path).is_ok());
+        fs::remove_file(path).unwrap();
+    }
+
+    //the images are changed minute by minute, so they're a little off the exact crossing times
+    fn close(time: Time, expected: Time) -> bool {
+        (time - expected)
+            .total_mins
+            .min((expected - time).total_mins)
+            <= 3
+    }
+
+    #[test]
+    fn elevation_timings_change_as_the_sun_passes_each_image() {
+        let day = date(2024, 3, 20);
+        let dir = wallpapers("elevations", &[("", 4)]);
+        let (lat, long) = LONDON;
+        let elevations = [-20.0, 0.0, 30.0, 5.0];
+        let (times, files) =
+            elevation_timings(dir.to_str().unwrap(), lat, long, Some(&elevations), day).unwrap();
+        let changes_to = |image: &str| -> Vec<Time> {
+            times
+                .iter()
+                .zip(files.iter())
+                .filter(|(_, file)| file.ends_with(image))
+                .map(|(time, _)| *time)
+                .collect()
+        };
+        //the image shown over midnight also starts at the start of the day
+        let assert_changes_at = |image: &str, expected: Time| {
+            let changes = changes_to(image);
+            assert!(
+                changes.iter().any(|time| close(*time, expected)),
+                "{} is shown at {:?} rather than {:?}",
+                image,
+                changes,
+                expected
+            );
+        };
+
+        //each image is shown from halfway between its elevation and the one before it
+        let at = |angle, direction| local_time(day, LONDON, angle, direction);
+        assert_changes_at("2.png", at(-10.0, Direction::Rising));
+        assert_changes_at("3.png", at(15.0, Direction::Rising));
+        assert_changes_at("4.png", at(17.5, Direction::Setting));
+        //the first image is only used while the sun is rising, so the last one stays up until
+        //the sun is at its lowest, and the images go round in order
+        let order = ["1.png", "2.png", "3.png", "4.png"];
+        let position = |file: &String| order.iter().position(|image| file.ends_with(image));
+        for pair in files.windows(2) {
+            let (from, to) = (position(&pair[0]).unwrap(), position(&pair[1]).unwrap());
+            assert_eq!((from + 1) % order.len(), to, "{:?}", files);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn elevation_timings_spread_the_images_without_elevations() {
+        let day = date(2024, 6, 21);
+        let dir = wallpapers("spread", &[("", 5)]);
+        let (lat, long) = LONDON;
+        let (times, files) =
+            elevation_timings(dir.to_str().unwrap(), lat, long, None, day).unwrap();
+
+        assert_eq!(times[0], Time::new(0));
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]));
+        //every image gets a turn, each once apart from the one shown over midnight
+        for image in 1..=5 {
+            let shown = files
+                .iter()
+                .filter(|file| file.ends_with(&format!("{}.png", image)))
+                .count();
+            assert!(
+                (1..=2).contains(&shown),
+                "{}.png is shown {} times",
+                image,
+                shown
+            );
+        }
+        assert!(files.len() <= 6);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn elevation_timings_need_an_elevation_for_each_image() {
+        let day = date(2024, 3, 20);
+        let (lat, long) = LONDON;
+        let dir = wallpapers("mismatch", &[("", 3)]);
+        let error = elevation_timings(dir.to_str().unwrap(), lat, long, Some(&[0.0, 10.0]), day)
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<Errors>(),
+            Some(Errors::ConfigFileError(
+                ConfigFileErrors::FileElevationMismatch
+            ))
+        ));
+
+        let empty = wallpapers("no-elevations", &[("", 0)]);
+        let error = elevation_timings(empty.to_str().unwrap(), lat, long, None, day).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<Errors>(),
+            Some(Errors::NoFilesFoundError(_))
+        ));
+        fs::remove_dir_all(dir).unwrap();
+        fs::remove_dir_all(empty).unwrap();
+    }
 }
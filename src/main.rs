@@ -17,87 +17,101 @@
    You should have received a copy of the GNU General Public License
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
-use crate::errors::{ConfigFileErrors, Errors};
 use clap::Parser;
 use dyn_wall_rs::{
-    auto_time_setup, check_dir_exists, config::Args, create_data_file, get_curr_back,
-    print_schedule, sun_timings, time_track::Time, update_wallpaper_days, wallpaper_listener,
+    config::{absolute_directory, Args, Subcommands},
+    control::{self, ControlCommand},
+    get_curr_back, heic, lock_instance, print_schedule, setup, slideshow, wallpaper_listener,
 };
 use std::env;
-use std::fs::canonicalize;
-use walkdir::WalkDir;
-
-pub mod config;
-pub mod errors;
-pub mod time_track;
+use std::path::Path;
+use std::process;
 
 fn main() {
     //convert to clap to add setting to print help message if no argument sent
     //and make help message order same as Args struct order
     let cli_args = Args::parse();
     let cli_args_used = !(Args::default() == cli_args);
-    //min depth of what files should be looked at, will remain as 1 if not syncing with sun, will
-    //change to 2 if syncing with sun to ignore the directory names, focusing just on the files
-    let mut min_depth = 1;
+    //subcommands don't touch the config file, so they're handled before it is read
+    if let Some(Subcommands::Import { file, output }) = &cli_args.command {
+        let output = output.to_owned().unwrap_or_else(|| {
+            Path::new(file)
+                .with_extension("")
+                .to_string_lossy()
+                .to_string()
+        });
+        if let Err(e) = heic::import(file, &output) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        return;
+    }
+    if let Some(command) = cli_args.command.as_ref().and_then(Subcommands::control) {
+        match control::send(command) {
+            Ok(reply) => println!("{}", reply.trim_end()),
+            Err(e) => eprintln!("{}", e),
+        }
+        return;
+    }
 
     match Args::mixed(cli_args, cli_args_used) {
         Err(e) => {
             eprintln!("{}", e);
         }
-        Ok(mut args) => {
-            //if day and night folders are being used, we need to only look at the second level of files
-            //(files in the folders) which is what changing min_depth to 2 accomplishes
-
+        Ok(args) => {
+            args.apply();
             if args.ret_curr_wp {
-                let curr_back = get_curr_back();
+                let curr_back = get_curr_back(args.backend.as_deref());
                 match curr_back {
                     Err(_) => print!("unable to retrieve current wallpaper"),
                     Ok(wp) => print!("{}", wp),
                 };
-            } else {
-                if args.lat.is_some() && args.long.is_some() {
-                    min_depth = 2;
+            } else if args.schedule || args.command.is_some() {
+                if let Err(e) = setup(args, vec![]).and_then(|setup| {
+                    if let Some(Subcommands::Export { output, transition }) = &setup.args.command {
+                        slideshow::export(
+                            &setup.dir,
+                            setup.min_depth,
+                            &setup.args,
+                            output,
+                            *transition,
+                        )
+                    } else {
+                        print_schedule(&setup.dir, setup.min_depth, setup.args)
+                    }
+                }) {
+                    eprintln!("{}", e);
                 }
-
-                if let Some(dir) = &args.directory {
-                    let dir = dir.as_str();
-                    let dir_count = WalkDir::new(dir).min_depth(min_depth).into_iter().count();
-                    let dir = canonicalize(dir).expect("Failed to canonicalize");
-                    let dir = dir.to_str().expect("Couldn't convert to string");
-                    create_data_file("curr").unwrap();
-
-                    if args.days.is_some() {
-                        let times: Vec<Time> = vec![Time::default()];
-                        args.times = Some(times);
-                        if create_data_file("visited_days").unwrap() || env::var("DYN_TEST").is_ok()
-                        {
-                            update_wallpaper_days(dir).unwrap();
-                        }
-                    } else if args.times.is_none() {
-                        if 1440 % dir_count != 0 || dir_count == 0 {
-                            eprintln!("{}", Errors::CountCompatError(dir_count));
-                        } else {
-                            let (step_time, mut loop_time) = auto_time_setup(dir);
-                            match step_time {
-                                Err(e) => eprintln!("{}", e),
-                                Ok(step_time) => {
-                                    let mut times: Vec<Time> = vec![];
-                                    for _ in 1..=dir_count {
-                                        times.push(loop_time);
-                                        loop_time += step_time;
-                                    }
-                                    args.times = Some(times);
-                                }
+            } else {
+                //taken before anything else so a second instance doesn't touch the data files
+                let _lock = match lock_instance() {
+                    Ok(lock) => lock,
+                    Err(e) => {
+                        //if the user asked for something different, the running instance is
+                        //told to use that instead
+                        if cli_args_used {
+                            let forwarded = ControlCommand::Args {
+                                cwd: env::current_dir()
+                                    .map(|cwd| cwd.to_string_lossy().to_string())
+                                    .unwrap_or_default(),
+                                args: env::args().skip(1).collect(),
+                            };
+                            if let Ok(reply) = control::send(forwarded) {
+                                println!("{}", reply.trim_end());
+                                return;
                             }
                         }
-                    }
-                    if args.schedule {
-                        if let Err(e) = print_schedule(dir, min_depth, args) {
-                            eprintln!("{}", e);
-                        }
-                    } else if let Err(e) = wallpaper_listener(String::from(dir), args, min_depth) {
                         eprintln!("{}", e);
+                        process::exit(1);
                     }
+                };
+                let cli = absolute_directory(
+                    &env::current_dir().unwrap_or_default(),
+                    env::args().skip(1).collect(),
+                );
+                if let Err(e) = setup(args, cli).and_then(wallpaper_listener) {
+                    eprintln!("{}", e);
+                    process::exit(1);
                 }
             }
         }
@@ -0,0 +1,557 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use crate::{control::Request, time_track::Time};
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use std::{
+    sync::mpsc::{Receiver, RecvTimeoutError, TryRecvError},
+    thread,
+    time::Instant,
+};
+
+//longest the scheduler sleeps in one go. The monotonic clock stops while the machine is
+//suspended, so waking up regularly is the only way to notice a suspend before the next change
+const MAX_SLEEP: std::time::Duration = std::time::Duration::from_secs(60);
+//how far the wall clock can drift from the monotonic clock during one sleep before it is
+//treated as a jump
+const JUMP_THRESHOLD_SECS: i64 = 5;
+
+///Source of the current time. The scheduler only ever looks at the time through this, so it can
+///be driven by a fake clock
+pub trait Clock {
+    ///Current local wall clock time
+    fn now(&self) -> NaiveDateTime;
+
+    ///Time elapsed since some fixed point, unaffected by changes to the wall clock
+    fn monotonic(&self) -> std::time::Duration;
+
+    ///Blocks for the given duration
+    fn sleep(&self, dur: std::time::Duration);
+
+    ///Blocks for the given duration, or until a request comes in. By default, requests that are
+    ///already waiting are taken and otherwise the clock sleeps for the whole duration
+    fn recv_timeout(
+        &self,
+        requests: &Receiver<Request>,
+        dur: std::time::Duration,
+    ) -> Result<Request, RecvTimeoutError> {
+        match requests.try_recv() {
+            Ok(request) => Ok(request),
+            Err(TryRecvError::Disconnected) => Err(RecvTimeoutError::Disconnected),
+            Err(TryRecvError::Empty) => {
+                self.sleep(dur);
+                Err(RecvTimeoutError::Timeout)
+            }
+        }
+    }
+}
+
+///The real clock, using the local timezone
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+
+    fn monotonic(&self) -> std::time::Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, dur: std::time::Duration) {
+        thread::sleep(dur);
+    }
+
+    fn recv_timeout(
+        &self,
+        requests: &Receiver<Request>,
+        dur: std::time::Duration,
+    ) -> Result<Request, RecvTimeoutError> {
+        requests.recv_timeout(dur)
+    }
+}
+
+///Why the scheduler woke up
+pub enum Wake {
+    ///A scheduled change is due
+    Scheduled,
+    ///The wall clock jumped, from a suspend and resume, an NTP correction or the time being
+    ///changed by hand, so the wallpaper may be out of date
+    ClockJump,
+    ///The date changed since the last wake up. Only used with `Interval::Times`, so schedules
+    ///that depend on the date (like sunrise and sunset) can be recomputed
+    NewDay,
+    ///A command came in through the control socket. Nothing is due, so the next change stays
+    ///where it was
+    Control(Request),
+}
+
+///When the wallpaper should change
+#[derive(Clone, Debug, PartialEq)]
+pub enum Interval {
+    ///At each of the times of the day, with the images matched to the times in order
+    Times(Vec<Time>),
+    ///At midnight, every given number of days
+    Days(u32),
+}
+
+pub struct Scheduler<C: Clock> {
+    clock: C,
+    interval: Interval,
+    next_run: NaiveDateTime,
+    day: NaiveDate,
+    requests: Option<Receiver<Request>>,
+}
+
+impl<C: Clock> Scheduler<C> {
+    pub fn new(clock: C, interval: Interval) -> Self {
+        let now = clock.now();
+        let next_run = match &interval {
+            Interval::Times(times) => next_change_or_midnight(times, now),
+            Interval::Days(_) => next_midnight(now),
+        };
+        Scheduler {
+            clock,
+            interval,
+            next_run,
+            day: now.date(),
+            requests: None,
+        }
+    }
+
+    ///Wakes the scheduler up with `Wake::Control` whenever a request comes in
+    pub fn listen(&mut self, requests: Receiver<Request>) {
+        self.requests = Some(requests);
+    }
+
+    ///Stops waking up for requests, handing back where they come from
+    pub fn stop_listening(&mut self) -> Option<Receiver<Request>> {
+        self.requests.take()
+    }
+
+    ///Replaces the times of the day the wallpaper changes at
+    pub fn set_times(&mut self, times: Vec<Time>) {
+        self.next_run = next_change_or_midnight(&times, self.clock.now());
+        self.interval = Interval::Times(times);
+    }
+
+    ///When the next change is due
+    pub fn next_run(&self) -> NaiveDateTime {
+        self.next_run
+    }
+
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    ///Sleeps until the next change is due, then moves on to the one after it. The wall clock
+    ///is checked again after every sleep, so waking up early just means sleeping again. Returns
+    ///early with `Wake::ClockJump` if the wall clock stops agreeing with the monotonic clock
+    pub fn wait(&mut self) -> Wake {
+        let wake = loop {
+            let now = self.clock.now();
+            if now >= self.next_run {
+                break Wake::Scheduled;
+            }
+            //to_std only fails on negative durations, which was ruled out above
+            let remaining = (self.next_run - now).to_std().unwrap_or_default();
+            let mono_before = self.clock.monotonic();
+            let sleep = remaining.min(MAX_SLEEP);
+            match &self.requests {
+                Some(requests) => match self.clock.recv_timeout(requests, sleep) {
+                    Ok(request) => return Wake::Control(request),
+                    Err(RecvTimeoutError::Timeout) => {}
+                    //nothing can send requests any more
+                    Err(RecvTimeoutError::Disconnected) => self.requests = None,
+                },
+                None => self.clock.sleep(sleep),
+            }
+
+            let wall_elapsed = self.clock.now() - now;
+            let mono_elapsed = self.clock.monotonic() - mono_before;
+            let drift = wall_elapsed - Duration::from_std(mono_elapsed).unwrap_or(wall_elapsed);
+            if drift.num_seconds().abs() >= JUMP_THRESHOLD_SECS {
+                if self.clock.now() >= self.next_run {
+                    break Wake::Scheduled;
+                }
+                break Wake::ClockJump;
+            }
+        };
+
+        let now = self.clock.now();
+        let new_day = now.date() != self.day;
+        self.day = now.date();
+
+        self.next_run = match (&self.interval, &wake) {
+            (Interval::Times(times), _) => next_change_or_midnight(times, now),
+            (Interval::Days(days), Wake::Scheduled) => {
+                let interval = Duration::days(i64::from((*days).max(1)));
+                let mut next_run = midnight(self.next_run.date()) + interval;
+                //after a suspend longer than the interval, the changes that were missed are
+                //skipped instead of all being made one after another
+                while next_run <= now {
+                    next_run += interval;
+                }
+                next_run
+            }
+            //the day count carries on from the last change regardless of what the clock did
+            (Interval::Days(_), _) => self.next_run,
+        };
+
+        match self.interval {
+            Interval::Times(_) if new_day => Wake::NewDay,
+            _ => wake,
+        }
+    }
+}
+
+///Returns the index of the time (and so the image) that is active at `curr_time`. This is the
+///latest time that has already passed today, or if none have passed yet, the latest time of the
+///day, since that image carries on from yesterday
+pub fn current_index(times: &[Time], curr_time: Time) -> usize {
+    let passed = times
+        .iter()
+        .enumerate()
+        .filter(|(_, time)| **time <= curr_time)
+        .max_by_key(|(_, time)| time.total_mins);
+
+    match passed {
+        Some((index, _)) => index,
+        None => times
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, time)| time.total_mins)
+            .map(|(index, _)| index)
+            .unwrap_or(0),
+    }
+}
+
+///Returns the first point in time after `now` at which one of the times is reached
+pub fn next_change(times: &[Time], now: NaiveDateTime) -> NaiveDateTime {
+    let today = now.date();
+    times
+        .iter()
+        .map(|time| {
+            let at = today.and_time(time_of_day(*time));
+            if at > now {
+                at
+            } else {
+                at + Duration::days(1)
+            }
+        })
+        .min()
+        .unwrap_or_else(|| next_midnight(now))
+}
+
+//times mode also wakes at midnight so the schedule for the new day can be worked out
+fn next_change_or_midnight(times: &[Time], now: NaiveDateTime) -> NaiveDateTime {
+    next_change(times, now).min(next_midnight(now))
+}
+
+///Converts the current time of day into a `Time`
+pub fn minute_of_day(now: NaiveDateTime) -> Time {
+    Time::new(now.hour() * 60 + now.minute())
+}
+
+fn time_of_day(time: Time) -> NaiveTime {
+    NaiveTime::from_hms_opt(time.hours % 24, time.mins % 60, 0).expect("Time out of range")
+}
+
+fn midnight(date: NaiveDate) -> NaiveDateTime {
+    date.and_hms_opt(0, 0, 0).expect("Midnight is always valid")
+}
+
+fn next_midnight(now: NaiveDateTime) -> NaiveDateTime {
+    midnight(now.date()) + Duration::days(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::ControlCommand;
+    use std::{cell::Cell, sync::mpsc::channel};
+
+    //a clock that only moves when it's slept on. The wall clock can be made to jump on the next
+    //sleep, like it does over a suspend, while the monotonic clock doesn't
+    struct FakeClock {
+        now: Cell<NaiveDateTime>,
+        mono: Cell<std::time::Duration>,
+        jump: Cell<Duration>,
+    }
+
+    impl FakeClock {
+        fn at(hours: u32, mins: u32) -> Self {
+            FakeClock {
+                now: Cell::new(at(0, hours, mins)),
+                mono: Cell::new(std::time::Duration::ZERO),
+                jump: Cell::new(Duration::zero()),
+            }
+        }
+
+        fn jump_on_next_sleep(&self, by: Duration) {
+            self.jump.set(by);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> NaiveDateTime {
+            self.now.get()
+        }
+
+        fn monotonic(&self) -> std::time::Duration {
+            self.mono.get()
+        }
+
+        fn sleep(&self, dur: std::time::Duration) {
+            let jump = self.jump.replace(Duration::zero());
+            self.now
+                .set(self.now.get() + Duration::from_std(dur).unwrap() + jump);
+            self.mono.set(self.mono.get() + dur);
+        }
+    }
+
+    fn at(day: u32, hours: u32, mins: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, 10 + day)
+            .unwrap()
+            .and_hms_opt(hours, mins, 0)
+            .unwrap()
+    }
+
+    fn times(times: &[(u32, u32)]) -> Vec<Time> {
+        times
+            .iter()
+            .map(|(hours, mins)| Time::new(hours * 60 + mins))
+            .collect()
+    }
+
+    //the image wallpaper_current_time used to pick, before the scheduler replaced it
+    fn old_index(times: &[Time], curr_time: Time) -> usize {
+        let full_day = Time::new(1440);
+        let mut times_iter = times.iter();
+        let mut loop_time = *times_iter.next().unwrap();
+        let mut next_time = *times_iter.next().unwrap_or(&full_day);
+        let mut found = None;
+        for index in 0..times.len() {
+            if loop_time > next_time {
+                loop_time = Time::new(0);
+            }
+            if found.is_none() && curr_time >= loop_time && curr_time < next_time {
+                found = Some(index);
+            }
+            loop_time = next_time;
+            next_time = *times_iter.next().unwrap_or(&times[0]);
+        }
+        found.unwrap_or(times.len() - 1)
+    }
+
+    #[test]
+    fn current_index_matches_old_selection() {
+        let schedules = [
+            times(&[(0, 0)]),
+            times(&[(0, 0), (8, 0), (16, 0)]),
+            times(&[(6, 0), (12, 0), (18, 0)]),
+            times(&[(1, 30), (7, 45), (13, 0), (23, 59)]),
+        ];
+        for schedule in &schedules {
+            for minute in 0..1440 {
+                let curr_time = Time::new(minute);
+                assert_eq!(
+                    current_index(schedule, curr_time),
+                    old_index(schedule, curr_time),
+                    "{:?} at {}",
+                    schedule,
+                    minute
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn current_index_wraps_past_midnight() {
+        //the image for the last time of the day carries on until the first one
+        let schedule = times(&[(6, 0), (12, 0), (18, 0)]);
+        assert_eq!(current_index(&schedule, Time::new(23 * 60)), 2);
+        assert_eq!(current_index(&schedule, Time::new(0)), 2);
+        assert_eq!(current_index(&schedule, Time::new(5 * 60 + 59)), 2);
+        assert_eq!(current_index(&schedule, Time::new(6 * 60)), 0);
+
+        //and a schedule that goes over midnight keeps showing the image from before it
+        let schedule = times(&[(22, 0), (2, 0), (8, 0)]);
+        assert_eq!(current_index(&schedule, Time::new(23 * 60)), 0);
+        assert_eq!(current_index(&schedule, Time::new(60)), 0);
+        assert_eq!(current_index(&schedule, Time::new(3 * 60)), 1);
+        assert_eq!(current_index(&schedule, Time::new(21 * 60)), 2);
+    }
+
+    #[test]
+    fn next_change_is_the_next_time_to_come() {
+        let schedule = times(&[(6, 0), (12, 0), (18, 0)]);
+        assert_eq!(next_change(&schedule, at(0, 5, 59)), at(0, 6, 0));
+        assert_eq!(next_change(&schedule, at(0, 12, 0)), at(0, 18, 0));
+        assert_eq!(next_change(&schedule, at(0, 23, 0)), at(1, 6, 0));
+        assert_eq!(next_change(&[], at(0, 23, 0)), at(1, 0, 0));
+    }
+
+    #[test]
+    fn next_change_agrees_with_current_index() {
+        //just before the next change the image is still the old one, and at it the image moves on
+        let schedule = times(&[(1, 30), (7, 45), (13, 0), (23, 59)]);
+        for minute in 0..1440 {
+            let now = at(0, minute / 60, minute % 60);
+            let next = next_change(&schedule, now);
+            let before = next - Duration::minutes(1);
+            assert_eq!(
+                current_index(&schedule, minute_of_day(before)),
+                current_index(&schedule, minute_of_day(now))
+            );
+            assert_ne!(
+                current_index(&schedule, minute_of_day(next)),
+                current_index(&schedule, minute_of_day(now))
+            );
+        }
+    }
+
+    #[test]
+    fn wait_sleeps_until_each_change() {
+        let schedule = times(&[(6, 0), (12, 0), (18, 0)]);
+        let mut scheduler = Scheduler::new(FakeClock::at(11, 0), Interval::Times(schedule));
+        assert_eq!(scheduler.next_run(), at(0, 12, 0));
+
+        assert!(matches!(scheduler.wait(), Wake::Scheduled));
+        assert_eq!(scheduler.clock().now(), at(0, 12, 0));
+        assert_eq!(scheduler.next_run(), at(0, 18, 0));
+
+        assert!(matches!(scheduler.wait(), Wake::Scheduled));
+        //the scheduler also wakes at midnight, so the schedule for the new day can be worked out
+        assert_eq!(scheduler.next_run(), at(1, 0, 0));
+        assert!(matches!(scheduler.wait(), Wake::NewDay));
+        assert_eq!(scheduler.next_run(), at(1, 6, 0));
+    }
+
+    #[test]
+    fn wait_for_days_changes_at_midnight() {
+        let mut scheduler = Scheduler::new(FakeClock::at(15, 0), Interval::Days(2));
+        assert!(matches!(scheduler.wait(), Wake::Scheduled));
+        assert_eq!(scheduler.clock().now(), at(1, 0, 0));
+        assert_eq!(scheduler.next_run(), at(3, 0, 0));
+    }
+
+    #[test]
+    fn listening_wait_uses_the_clock() {
+        let schedule = times(&[(12, 0)]);
+        let mut scheduler = Scheduler::new(FakeClock::at(11, 0), Interval::Times(schedule));
+        let (requests, received) = channel();
+        scheduler.listen(received);
+
+        //nothing is sent, so this only returns once the fake clock reaches the change
+        assert!(matches!(scheduler.wait(), Wake::Scheduled));
+        assert_eq!(scheduler.clock().now(), at(0, 12, 0));
+
+        let (reply, _) = channel();
+        requests
+            .send(Request {
+                command: ControlCommand::Next,
+                reply,
+            })
+            .unwrap();
+        let wake = scheduler.wait();
+        assert!(matches!(
+            wake,
+            Wake::Control(Request {
+                command: ControlCommand::Next,
+                ..
+            })
+        ));
+        assert_eq!(scheduler.clock().now(), at(0, 12, 0));
+    }
+
+    #[test]
+    fn wall_clock_jump_wakes_early() {
+        let schedule = times(&[(6, 0), (12, 0), (18, 0)]);
+        let mut scheduler = Scheduler::new(FakeClock::at(8, 0), Interval::Times(schedule));
+        //the machine is suspended for an hour during the first sleep
+        scheduler.clock().jump_on_next_sleep(Duration::hours(1));
+
+        assert!(matches!(scheduler.wait(), Wake::ClockJump));
+        assert_eq!(scheduler.clock().now(), at(0, 9, 1));
+        //the change that was due is still coming up
+        assert_eq!(scheduler.next_run(), at(0, 12, 0));
+
+        assert!(matches!(scheduler.wait(), Wake::Scheduled));
+        assert_eq!(scheduler.clock().now(), at(0, 12, 0));
+    }
+
+    #[test]
+    fn wall_clock_jump_past_a_change_is_scheduled() {
+        let schedule = times(&[(6, 0), (12, 0), (18, 0)]);
+        let mut scheduler = Scheduler::new(FakeClock::at(8, 0), Interval::Times(schedule));
+        scheduler.clock().jump_on_next_sleep(Duration::hours(5));
+
+        assert!(matches!(scheduler.wait(), Wake::Scheduled));
+        assert_eq!(scheduler.clock().now(), at(0, 13, 1));
+        assert_eq!(scheduler.next_run(), at(0, 18, 0));
+    }
+
+    #[test]
+    fn wall_clock_jump_under_threshold_is_ignored() {
+        let schedule = times(&[(12, 0)]);
+        let mut scheduler = Scheduler::new(FakeClock::at(11, 0), Interval::Times(schedule));
+        scheduler
+            .clock()
+            .jump_on_next_sleep(Duration::seconds(JUMP_THRESHOLD_SECS - 1));
+
+        assert!(matches!(scheduler.wait(), Wake::Scheduled));
+        assert!(scheduler.clock().now() >= at(0, 12, 0));
+    }
+
+    #[test]
+    fn wall_clock_jump_past_several_days_changes_once() {
+        let mut scheduler = Scheduler::new(FakeClock::at(15, 0), Interval::Days(2));
+        //suspended for longer than the interval, past the changes on days 1 and 3
+        scheduler.clock().jump_on_next_sleep(Duration::days(5));
+
+        assert!(matches!(scheduler.wait(), Wake::Scheduled));
+        assert!(scheduler.clock().now() > at(5, 15, 0));
+        //the day count carries on from the changes that were missed
+        assert_eq!(scheduler.next_run(), at(7, 0, 0));
+
+        assert!(matches!(scheduler.wait(), Wake::Scheduled));
+        assert_eq!(scheduler.clock().now(), at(7, 0, 0));
+        assert_eq!(scheduler.next_run(), at(9, 0, 0));
+    }
+
+    #[test]
+    fn wall_clock_jump_keeps_the_day_count() {
+        let mut scheduler = Scheduler::new(FakeClock::at(15, 0), Interval::Days(2));
+        scheduler.clock().jump_on_next_sleep(Duration::hours(3));
+
+        assert!(matches!(scheduler.wait(), Wake::ClockJump));
+        assert_eq!(scheduler.next_run(), at(1, 0, 0));
+    }
+}
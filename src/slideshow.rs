@@ -0,0 +1,323 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//GNOME background slideshows. These are XML files listing each image (static) with how long it
+//is shown for in seconds, and the crossfades (transitions) between them, starting from a given
+//time and looping once the end is reached
+use crate::{config::Args, errors::Errors, schedule_files, time_track::Time};
+use roxmltree::{Document, Node};
+use std::{error::Error, fs, path::Path};
+
+const DAY_SECS: f64 = 86400.0;
+
+///Whether the path points to a GNOME slideshow rather than a directory of images
+pub fn is_slideshow(path: &str) -> bool {
+    let path = Path::new(path);
+    path.is_file()
+        && path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("xml"))
+}
+
+///Reads the slideshow at `path` into the times of the day the wallpaper changes at and the image
+///for each of them. Slideshows shorter than a day are repeated to fill it, and images shown for
+///less than a minute are skipped, since times only go down to the minute
+pub fn load(path: &str) -> Result<(Vec<Time>, Vec<String>), Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    let base = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    Ok(parse(&contents, base)?)
+}
+
+fn parse(xml: &str, base: &Path) -> Result<(Vec<Time>, Vec<String>), Errors> {
+    let doc = Document::parse(xml)
+        .map_err(|e| Errors::ImportError(format!("invalid slideshow: {}", e)))?;
+    let root = doc.root_element();
+
+    let start = child(root, "starttime")
+        .map(|starttime| {
+            Ok::<_, Errors>(
+                number(starttime, "hour")? * 3600.0
+                    + number(starttime, "minute")? * 60.0
+                    + number(starttime, "second").unwrap_or(0.0),
+            )
+        })
+        .transpose()?
+        .unwrap_or(0.0);
+
+    //offset from the start time that each image is shown at
+    let mut statics: Vec<(f64, String)> = vec![];
+    let mut length = 0.0;
+    for node in root.children().filter(|node| node.is_element()) {
+        match node.tag_name().name() {
+            "static" => {
+                let file = child(node, "file").and_then(file_path).ok_or_else(|| {
+                    Errors::ImportError(String::from("slideshow image has no file"))
+                })?;
+                let file = base.join(file.trim());
+                statics.push((length, file.to_string_lossy().to_string()));
+                length += number(node, "duration")?;
+            }
+            "transition" => length += number(node, "duration")?,
+            _ => {}
+        }
+    }
+    if statics.is_empty() || length <= 0.0 {
+        return Err(Errors::ImportError(String::from("slideshow has no images")));
+    }
+
+    let mut times: Vec<Time> = vec![];
+    let mut files = vec![];
+    let mut cycle = 0.0;
+    while cycle < DAY_SECS {
+        for (offset, file) in statics.iter() {
+            if cycle + offset >= DAY_SECS {
+                break;
+            }
+            let mins = ((start + cycle + offset) / 60.0).floor() as u32 % 1440;
+            let time = Time::new(mins);
+            if !times.contains(&time) {
+                times.push(time);
+                files.push(file.to_owned());
+            }
+        }
+        cycle += length;
+    }
+    Ok((times, files))
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn number(node: Node, name: &str) -> Result<f64, Errors> {
+    child(node, name)
+        .and_then(|child| child.text())
+        .and_then(|text| text.trim().parse().ok())
+        .ok_or_else(|| Errors::ImportError(format!("slideshow has an invalid {}", name)))
+}
+
+//the file can be given once, or once per screen size inside size elements, in which case the
+//first one is used
+fn file_path<'a>(file: Node<'a, '_>) -> Option<&'a str> {
+    match child(file, "size") {
+        Some(size) => size.text(),
+        None => file.text(),
+    }
+}
+
+///Writes the schedule for the directory as a GNOME slideshow to `output`, with a crossfade
+///lasting `transition` seconds leading into each change
+pub fn export(
+    dir: &str,
+    min_depth: usize,
+    args: &Args,
+    output: &str,
+    transition: f64,
+) -> Result<(), Box<dyn Error>> {
+    let files = match &args.files {
+        Some(files) => files.to_owned(),
+        None => schedule_files(dir, min_depth)?,
+    };
+    let times = args.times.as_deref().unwrap_or_default();
+    if times.len() != files.len() {
+        return Err(Errors::ConfigFileError(crate::ConfigFileErrors::FileTimeMismatch).into());
+    }
+
+    fs::write(output, to_xml(times, &files, transition)?)?;
+    println!("Slideshow has been written to {}", output);
+    Ok(())
+}
+
+fn to_xml(times: &[Time], files: &[String], transition: f64) -> Result<String, Errors> {
+    let first = times
+        .first()
+        .ok_or(Errors::ConfigFileError(crate::ConfigFileErrors::Empty))?;
+    let mut xml = format!(
+        "<background>\n  <starttime>\n    <year>2000</year>\n    <month>1</month>\n    <day>1</day>\n    <hour>{}</hour>\n    <minute>{}</minute>\n    <second>0</second>\n  </starttime>\n",
+        first.hours, first.mins
+    );
+
+    for (index, (time, file)) in times.iter().zip(files.iter()).enumerate() {
+        let next = (index + 1) % times.len();
+        //the last image lasts until the first one comes back round the next day
+        let mut mins = (times[next].total_mins as i64 - time.total_mins as i64).rem_euclid(1440);
+        if mins == 0 {
+            mins = 1440;
+        }
+        let secs = mins as f64 * 60.0;
+        let fade = if times.len() > 1 { transition } else { 0.0 };
+        if fade >= secs {
+            return Err(Errors::ImportError(format!(
+                "the transition is longer than the {} minutes {} is shown for",
+                mins, file
+            )));
+        }
+
+        xml.push_str(&format!(
+            "  <static>\n    <duration>{:.1}</duration>\n    <file>{}</file>\n  </static>\n",
+            secs - fade,
+            escape(file)
+        ));
+        if fade > 0.0 {
+            xml.push_str(&format!(
+                "  <transition type=\"overlay\">\n    <duration>{:.1}</duration>\n    <from>{}</from>\n    <to>{}</to>\n  </transition>\n",
+                fade,
+                escape(file),
+                escape(&files[next])
+            ));
+        }
+    }
+    xml.push_str("</background>\n");
+    Ok(xml)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn times(times: &[&str]) -> Vec<Time> {
+        times.iter().map(|time| time.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn parse_starts_at_the_start_time() {
+        let xml = r#"<background>
+  <starttime><year>2011</year><month>11</month><day>24</day><hour>7</hour><minute>30</minute><second>0</second></starttime>
+  <static><duration>43140.0</duration><file>day.jpg</file></static>
+  <transition type="overlay"><duration>60.0</duration><from>day.jpg</from><to>night.jpg</to></transition>
+  <static><duration>43200.0</duration><file><size width="1920" height="1080">/usr/share/night.jpg</size><size width="800" height="600">/usr/share/small.jpg</size></file></static>
+</background>"#;
+        let (parsed_times, files) = parse(xml, Path::new("/walls")).unwrap();
+        assert_eq!(parsed_times, times(&["07:30", "19:30"]));
+        assert_eq!(files, ["/walls/day.jpg", "/usr/share/night.jpg"]);
+    }
+
+    #[test]
+    fn short_slideshows_repeat_through_the_day() {
+        //two images lasting three hours between them, crossfading into each other
+        let xml = r#"<background>
+  <static><duration>3540</duration><file>a.png</file></static>
+  <transition><duration>60</duration><from>a.png</from><to>b.png</to></transition>
+  <static><duration>7140</duration><file>b.png</file></static>
+  <transition><duration>60</duration><from>b.png</from><to>a.png</to></transition>
+</background>"#;
+        let (parsed_times, files) = parse(xml, Path::new("")).unwrap();
+        let expected: Vec<Time> = (0..8)
+            .flat_map(|cycle| vec![Time::new(cycle * 180), Time::new(cycle * 180 + 60)])
+            .collect();
+        assert_eq!(parsed_times, expected);
+        assert_eq!(files.len(), 16);
+        assert!(files.chunks(2).all(|pair| pair == ["a.png", "b.png"]));
+    }
+
+    #[test]
+    fn parse_rejects_slideshows_without_images() {
+        let invalid = [
+            "<background/>",
+            "<background><static><duration>60</duration></static></background>",
+            "<background><static><file>a.png</file></static></background>",
+            "<background><static><duration>0</duration><file>a.png</file></static></background>",
+            "<background><starttime><hour>x</hour></starttime></background>",
+            "not xml",
+        ];
+        for xml in Vec::from(invalid) {
+            assert!(
+                matches!(parse(xml, Path::new("")), Err(Errors::ImportError(_))),
+                "{}",
+                xml
+            );
+        }
+    }
+
+    #[test]
+    fn to_xml_round_trips() {
+        let schedule = times(&["06:15", "12:00", "19:45"]);
+        let files = vec![
+            String::from("/walls/1.png"),
+            String::from("/walls/a & b.png"),
+            String::from("/walls/3.png"),
+        ];
+        for transition in Vec::from([0.0, 300.0]) {
+            let xml = to_xml(&schedule, &files, transition).unwrap();
+            assert_eq!(xml.contains("<transition"), transition > 0.0);
+            assert!(xml.contains("a &amp; b.png"));
+            assert_eq!(
+                parse(&xml, Path::new("")).unwrap(),
+                (schedule.clone(), files.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn to_xml_checks_the_transition_fits() {
+        let schedule = times(&["06:00", "06:05"]);
+        let files = vec![String::from("1.png"), String::from("2.png")];
+        assert!(to_xml(&schedule, &files, 299.0).is_ok());
+        assert!(matches!(
+            to_xml(&schedule, &files, 300.0),
+            Err(Errors::ImportError(_))
+        ));
+        //one image has nothing to fade into, so it stays the whole day
+        let xml = to_xml(&schedule[..1], &files[..1], 300.0).unwrap();
+        assert!(xml.contains("<duration>86400.0</duration>"));
+        assert!(!xml.contains("<transition"));
+        assert!(to_xml(&[], &[], 0.0).is_err());
+    }
+
+    #[test]
+    fn export_writes_a_slideshow_load_reads() {
+        let dir = env::temp_dir().join(format!("dyn-wall-rs-slideshow-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in Vec::from(["1.png", "2.png", "3.png"]) {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let output = dir.join("slideshow.xml");
+        let output = output.to_str().unwrap();
+        let dir_str = dir.to_str().unwrap();
+        let args = Args {
+            times: Some(times(&["08:00", "16:00", "23:30"])),
+            ..Args::default()
+        };
+
+        export(dir_str, 1, &args, output, 60.0).unwrap();
+        assert!(is_slideshow(output));
+        let (loaded_times, files) = load(output).unwrap();
+        assert_eq!(loaded_times, times(&["08:00", "16:00", "23:30"]));
+        let names: Vec<&str> = files
+            .iter()
+            .map(|file| Path::new(file).file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, ["1.png", "2.png", "3.png"]);
+
+        let mismatched = Args {
+            times: Some(times(&["08:00"])),
+            ..Args::default()
+        };
+        assert!(export(dir_str, 1, &mismatched, output, 60.0).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
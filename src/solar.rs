@@ -0,0 +1,396 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//see https://en.wikipedia.org/wiki/Sunrise_equation
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+const ARGUMENT_OF_PERIHELION: f64 = 102.9372;
+const AXIAL_TILT: f64 = 23.44;
+const HOUR_ANGLE_TO_MINUTES_FACTOR: f64 = 4.0;
+///Elevation of the sun at the end of the morning golden hour and start of the evening one
+pub const GOLDEN_HOUR_ANGLE: f64 = 6.0;
+
+///Whether the sun is on its way up or down
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Rising,
+    Setting,
+}
+
+//position of the sun for a given day: when it is highest in the sky, and its declination
+struct SolarDay {
+    transit: DateTime<Utc>,
+    declination: f64,
+}
+
+fn solar_day(date: NaiveDate, long: f64) -> SolarDay {
+    let jan_2000 = NaiveDate::from_ymd_opt(2000, 1, 1).expect("Valid date");
+    let noon_2000 = Utc.from_utc_datetime(&jan_2000.and_hms_opt(12, 0, 0).expect("Valid time"));
+    let days_since_2000 = date.signed_duration_since(jan_2000).num_days() as f64;
+
+    let mean_solar_time = days_since_2000 + 0.0008 - (long / 360.0);
+    let solar_mean_anomaly = (357.5291 + 0.98560028 * mean_solar_time).rem_euclid(360.0);
+    let center = 1.9148 * solar_mean_anomaly.to_radians().sin()
+        + 0.0200 * (2.0 * solar_mean_anomaly).to_radians().sin()
+        + 0.0003 * (3.0 * solar_mean_anomaly).to_radians().sin();
+    let ecliptic_longitude =
+        (solar_mean_anomaly + center + 180.0 + ARGUMENT_OF_PERIHELION).rem_euclid(360.0);
+
+    let declination =
+        (ecliptic_longitude.to_radians().sin() * AXIAL_TILT.to_radians().sin()).asin();
+    let solar_transit = mean_solar_time + 0.0053 * solar_mean_anomaly.to_radians().sin()
+        - 0.0069 * (2.0 * ecliptic_longitude).to_radians().sin();
+
+    SolarDay {
+        transit: noon_2000 + Duration::seconds((solar_transit * 86400.0).round() as i64),
+        declination,
+    }
+}
+
+///Angle below the horizon the centre of the sun is at when it rises or sets, taking refraction,
+///the size of the sun, and the elevation of the observer (in meters) into account
+pub fn horizon_angle(elevation: f64) -> f64 {
+    -0.83 - 2.076 * elevation.max(0.0).sqrt() / 60.0
+}
+
+///Whether, and when, the sun crosses an angle of elevation on a given day
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Crossing {
+    At(DateTime<Utc>),
+    ///The sun stays above the angle all day, such as the midnight sun during a polar day
+    AlwaysAbove,
+    ///The sun stays below the angle all day, such as during a polar night
+    AlwaysBelow,
+}
+
+impl Crossing {
+    pub fn time(self) -> Option<DateTime<Utc>> {
+        match self {
+            Crossing::At(time) => Some(time),
+            _ => None,
+        }
+    }
+}
+
+///Returns when the sun crosses `angle` degrees of elevation on the given date
+pub fn time_at_elevation(
+    date: NaiveDate,
+    lat: f64,
+    long: f64,
+    angle: f64,
+    direction: Direction,
+) -> Crossing {
+    let day = solar_day(date, long);
+    let cos_hour_angle = (angle.to_radians().sin()
+        - lat.to_radians().sin() * day.declination.sin())
+        / (lat.to_radians().cos() * day.declination.cos());
+    //past either end, the hour angle would have to be imaginary, meaning the sun never gets to
+    //the angle
+    if cos_hour_angle < -1.0 {
+        return Crossing::AlwaysAbove;
+    } else if cos_hour_angle > 1.0 {
+        return Crossing::AlwaysBelow;
+    }
+
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+    let minutes = Duration::minutes((hour_angle * HOUR_ANGLE_TO_MINUTES_FACTOR).round() as i64);
+    match direction {
+        Direction::Rising => Crossing::At(day.transit - minutes),
+        Direction::Setting => Crossing::At(day.transit + minutes),
+    }
+}
+
+///Returns the elevation of the sun in degrees at the given time, and whether it is rising or
+///setting
+pub fn position(at: DateTime<Utc>, lat: f64, long: f64) -> (f64, Direction) {
+    let day = solar_day(at.date_naive(), long);
+    //the sun moves one degree of hour angle every 4 minutes
+    let seconds_from_transit = (at - day.transit).num_seconds() as f64;
+    let hour_angle = (seconds_from_transit / (HOUR_ANGLE_TO_MINUTES_FACTOR * 60.0) + 180.0)
+        .rem_euclid(360.0)
+        - 180.0;
+
+    let elevation = (lat.to_radians().sin() * day.declination.sin()
+        + lat.to_radians().cos() * day.declination.cos() * hour_angle.to_radians().cos())
+    .asin()
+    .to_degrees();
+    let direction = if hour_angle < 0.0 {
+        Direction::Rising
+    } else {
+        Direction::Setting
+    };
+    (elevation, direction)
+}
+
+///Returns the lowest and highest elevation the sun reaches on the given date
+pub fn elevation_range(date: NaiveDate, lat: f64, long: f64) -> (f64, f64) {
+    let transit = solar_day(date, long).transit;
+    let (highest, _) = position(transit, lat, long);
+    let (lowest, _) = position(transit + Duration::hours(12), lat, long);
+    (lowest, highest)
+}
+
+///Spreads `count` images evenly from the lowest elevation up to the highest one and back down,
+///following the path of the sun through the day
+pub fn spread_elevations(count: usize, lowest: f64, highest: f64) -> Vec<f64> {
+    (0..count)
+        .map(|i| {
+            let turn = 2.0 * std::f64::consts::PI * i as f64 / count as f64;
+            lowest + (highest - lowest) * (1.0 - turn.cos()) / 2.0
+        })
+        .collect()
+}
+
+///Returns the index of the image whose elevation is closest to `elevation`. The images are in
+///the order they are shown through the day, so the ones up to the highest elevation are used
+///while the sun is rising, and the ones from the highest elevation on while it is setting
+pub fn nearest_elevation(elevations: &[f64], elevation: f64, direction: Direction) -> usize {
+    let peak = elevations.iter().enumerate().fold(
+        0,
+        |peak, (i, e)| if *e > elevations[peak] { i } else { peak },
+    );
+    //images that are as close as each other go to the one the sun reaches first, so an image
+    //at the same elevation as the highest one still gets shown as the sun sets
+    let candidates: Vec<usize> = match direction {
+        Direction::Rising => (0..peak + 1).collect(),
+        Direction::Setting => (peak..elevations.len()).rev().collect(),
+    };
+
+    candidates
+        .into_iter()
+        .min_by(|a, b| {
+            let a = (elevations[*a] - elevation).abs();
+            let b = (elevations[*b] - elevation).abs();
+            a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .unwrap_or(0)
+}
+
+///How far below the horizon the sun goes before dawn starts and after dusk ends
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Twilight {
+    #[default]
+    Civil,
+    Nautical,
+    Astronomical,
+}
+
+impl Twilight {
+    pub fn angle(&self) -> f64 {
+        match self {
+            Twilight::Civil => -6.0,
+            Twilight::Nautical => -12.0,
+            Twilight::Astronomical => -18.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LONDON: (f64, f64) = (51.5074, -0.1278);
+    const NEW_YORK: (f64, f64) = (40.7128, -74.006);
+    const SYDNEY: (f64, f64) = (-33.8688, 151.2093);
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn utc(date: NaiveDate, h: u32, m: u32) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&date.and_hms_opt(h, m, 0).unwrap())
+    }
+
+    fn crossing(date: NaiveDate, (lat, long): (f64, f64), direction: Direction) -> DateTime<Utc> {
+        time_at_elevation(date, lat, long, horizon_angle(0.0), direction)
+            .time()
+            .unwrap()
+    }
+
+    //the equation is a few minutes out from the almanac at most
+    fn assert_near(time: DateTime<Utc>, expected: DateTime<Utc>) {
+        let off = (time - expected).num_minutes().abs();
+        assert!(off <= 3, "{} is {} minutes from {}", time, off, expected);
+    }
+
+    #[test]
+    fn sunrise_and_sunset_match_known_times() {
+        //published times, in UTC
+        let days = [
+            (LONDON, date(2024, 6, 21), (3, 43), (20, 21), 0),
+            (NEW_YORK, date(2024, 12, 21), (12, 16), (21, 32), 0),
+            //sydney is far enough ahead of utc that the sun rises the day before there
+            (SYDNEY, date(2024, 3, 20), (20, 2), (8, 10), -1),
+        ];
+        for (place, day, rise, set, rise_day) in Vec::from(days) {
+            let rise_date = day + Duration::days(rise_day);
+            assert_near(
+                crossing(day, place, Direction::Rising),
+                utc(rise_date, rise.0, rise.1),
+            );
+            assert_near(
+                crossing(day, place, Direction::Setting),
+                utc(day, set.0, set.1),
+            );
+        }
+    }
+
+    #[test]
+    fn twilight_and_golden_hour_come_in_order() {
+        let day = date(2024, 3, 20);
+        let (lat, long) = LONDON;
+        let at = |angle, direction| {
+            time_at_elevation(day, lat, long, angle, direction)
+                .time()
+                .unwrap()
+        };
+        let crossings = [
+            at(Twilight::Astronomical.angle(), Direction::Rising),
+            at(Twilight::Nautical.angle(), Direction::Rising),
+            at(Twilight::Civil.angle(), Direction::Rising),
+            at(horizon_angle(0.0), Direction::Rising),
+            at(GOLDEN_HOUR_ANGLE, Direction::Rising),
+            at(GOLDEN_HOUR_ANGLE, Direction::Setting),
+            at(horizon_angle(0.0), Direction::Setting),
+            at(Twilight::Civil.angle(), Direction::Setting),
+            at(Twilight::Nautical.angle(), Direction::Setting),
+            at(Twilight::Astronomical.angle(), Direction::Setting),
+        ];
+        assert!(crossings.windows(2).all(|pair| pair[0] < pair[1]));
+        //the sun is higher up from a mountain, so it rises earlier
+        assert!(
+            time_at_elevation(day, lat, long, horizon_angle(1000.0), Direction::Rising)
+                .time()
+                .unwrap()
+                < crossings[3]
+        );
+    }
+
+    #[test]
+    fn angles_the_sun_never_reaches_are_not_crossed() {
+        //london in midsummer never gets dark enough for astronomical twilight
+        let (lat, long) = LONDON;
+        assert_eq!(
+            time_at_elevation(date(2024, 6, 21), lat, long, -18.0, Direction::Rising),
+            Crossing::AlwaysAbove
+        );
+        //and never gets the sun 70° up
+        assert_eq!(
+            time_at_elevation(date(2024, 6, 21), lat, long, 70.0, Direction::Setting),
+            Crossing::AlwaysBelow
+        );
+    }
+
+    #[test]
+    fn position_agrees_with_the_crossings() {
+        for (place, day) in Vec::from([
+            (LONDON, date(2024, 6, 21)),
+            (NEW_YORK, date(2024, 12, 21)),
+            (SYDNEY, date(2024, 3, 20)),
+        ]) {
+            let (lat, long) = place;
+            let (elevation, direction) =
+                position(crossing(day, place, Direction::Rising), lat, long);
+            assert!(
+                (elevation - horizon_angle(0.0)).abs() < 0.3,
+                "{}",
+                elevation
+            );
+            assert_eq!(direction, Direction::Rising);
+            let (elevation, direction) =
+                position(crossing(day, place, Direction::Setting), lat, long);
+            assert!(
+                (elevation - horizon_angle(0.0)).abs() < 0.3,
+                "{}",
+                elevation
+            );
+            assert_eq!(direction, Direction::Setting);
+        }
+    }
+
+    #[test]
+    fn highest_point_is_at_noon() {
+        //at noon the sun is 90° less the distance between the latitude and the declination,
+        //which is the axial tilt at the solstices and 0 at the equinoxes
+        let (lat, long) = LONDON;
+        let (lowest, highest) = elevation_range(date(2024, 6, 21), lat, long);
+        assert!(
+            (highest - (90.0 - lat + AXIAL_TILT)).abs() < 0.2,
+            "{}",
+            highest
+        );
+        assert!(
+            (lowest - (lat + AXIAL_TILT - 90.0)).abs() < 0.2,
+            "{}",
+            lowest
+        );
+        let (_, highest) = elevation_range(date(2024, 3, 20), 0.0, 0.0);
+        assert!(highest > 89.5, "{}", highest);
+    }
+
+    #[test]
+    fn elevations_are_spread_up_and_back_down() {
+        let assert_spread = |spread: Vec<f64>, expected: &[f64]| {
+            assert_eq!(spread.len(), expected.len());
+            for (elevation, expected) in spread.iter().zip(expected.iter()) {
+                assert!((elevation - expected).abs() < 1e-9, "{:?}", spread);
+            }
+        };
+        assert_spread(
+            spread_elevations(4, -40.0, 60.0),
+            &[-40.0, 10.0, 60.0, 10.0],
+        );
+        assert_spread(
+            spread_elevations(6, -30.0, 30.0),
+            &[-30.0, -15.0, 15.0, 30.0, 15.0, -15.0],
+        );
+        assert_eq!(spread_elevations(1, -40.0, 60.0), [-40.0]);
+        assert!(spread_elevations(0, -40.0, 60.0).is_empty());
+    }
+
+    #[test]
+    fn nearest_elevation_depends_on_where_the_sun_is_going() {
+        let elevations = [-20.0, 0.0, 30.0, 5.0];
+        let nearest = |elevation, direction| nearest_elevation(&elevations, elevation, direction);
+        assert_eq!(nearest(-50.0, Direction::Rising), 0);
+        assert_eq!(nearest(-9.0, Direction::Rising), 1);
+        assert_eq!(nearest(16.0, Direction::Rising), 2);
+        assert_eq!(nearest(90.0, Direction::Rising), 2);
+        //only the highest image and the ones after it are used while the sun sets
+        assert_eq!(nearest(18.0, Direction::Setting), 2);
+        assert_eq!(nearest(17.0, Direction::Setting), 3);
+        assert_eq!(nearest(-50.0, Direction::Setting), 3);
+
+        //of two images at the top, the first is shown until noon and the second after it
+        let top = [-10.0, 40.0, 40.0, -5.0];
+        assert_eq!(nearest_elevation(&top, 41.0, Direction::Rising), 1);
+        assert_eq!(nearest_elevation(&top, 41.0, Direction::Setting), 2);
+        assert_eq!(nearest_elevation(&top, 30.0, Direction::Setting), 2);
+
+        //with the highest image last, it's the only one left for the evening
+        assert_eq!(
+            nearest_elevation(&[-10.0, 40.0], -10.0, Direction::Setting),
+            1
+        );
+        assert_eq!(nearest_elevation(&[12.0], -10.0, Direction::Rising), 0);
+    }
+}
@@ -24,7 +24,7 @@ use std::{
     str::FromStr,
 };
 
-#[derive(Copy, Clone, PartialOrd, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialOrd, PartialEq, Debug, Default)]
 pub struct Time {
     pub total_mins: u32,
     pub hours: u32,
@@ -53,6 +53,7 @@ impl Time {
 impl FromStr for Time {
     type Err = Errors;
 
+    #[allow(clippy::unnecessary_lazy_evaluations)]
     fn from_str(time_str: &str) -> Result<Self, Self::Err> {
         let mut time_split = time_str.split(':');
         let hours = time_split
@@ -93,11 +94,16 @@ impl AddAssign for Time {
     }
 }
 
+//subtraction wraps around midnight rather than underflowing, so 01:00 - 02:00 is 23:00
+fn wrapping_sub(total_mins: u32, other: u32) -> Time {
+    Time::new((total_mins as i64 - other as i64).rem_euclid(24 * 60) as u32)
+}
+
 impl Sub<Time> for Time {
     type Output = Time;
 
     fn sub(self, other: Time) -> Time {
-        Time::new(self.total_mins - other.total_mins)
+        wrapping_sub(self.total_mins, other.total_mins)
     }
 }
 
@@ -105,22 +111,33 @@ impl Sub<u32> for Time {
     type Output = Time;
 
     fn sub(self, other: u32) -> Time {
-        Time::new(self.total_mins - other)
+        wrapping_sub(self.total_mins, other)
     }
 }
 
 impl SubAssign for Time {
     fn sub_assign(&mut self, other: Self) {
-        *self = Time::new(self.total_mins - other.total_mins);
+        *self = wrapping_sub(self.total_mins, other.total_mins);
     }
 }
 
-impl Default for Time {
-    fn default() -> Time {
-        Time {
-            total_mins: 0,
-            hours: 0,
-            mins: 0,
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subtraction_wraps_around_midnight() {
+        assert_eq!(Time::new(60) - Time::new(120), Time::new(23 * 60));
+        assert_eq!(Time::new(120) - Time::new(60), Time::new(60));
+        assert_eq!(Time::new(0) - 1, Time::new(1439));
+        assert_eq!(Time::new(300) - 300, Time::new(0));
+        let mut time = Time::new(30);
+        time -= Time::new(1439);
+        assert_eq!(time, Time::new(31));
+        //the whole of sunset to sunrise, the way the schedule spans the night
+        assert_eq!(
+            Time::new(6 * 60) - Time::new(18 * 60 + 30),
+            Time::new(11 * 60 + 30)
+        );
     }
 }
@@ -0,0 +1,158 @@
+/*
+   dyn-wall-rs 2.1.3
+   Rehan Rana <rehanalirana@tuta.io>
+   Helps user set a dynamic wallpaper and lockscreen. For more info and help, go to https://github.com/RAR27/dyn-wall-rs
+   Copyright (C) 2020  Rehan Rana
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU General Public License for more details.
+
+   You should have received a copy of the GNU General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//things outside of the daemon that make it reload, like the config file being edited or images
+//being added
+use crate::{
+    config::config_path,
+    control::{self, ControlCommand, Request},
+    Setup,
+};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    error::Error,
+    path::Path,
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+//editors often save a file in a few steps (writing a temporary file then renaming it over the
+//old one), so changes this close together are treated as one
+const SETTLE_TIME: Duration = Duration::from_millis(250);
+
+///Tells the daemon to reload whenever the config file changes
+pub fn watch_config(requests: Sender<Request>) -> Result<(), Box<dyn Error>> {
+    let path = config_path().ok_or("Unable to find the config directory")?;
+    //the directory is watched rather than the file, since saving can replace the file entirely
+    let dir = path
+        .parent()
+        .ok_or("Unable to find the config directory")?
+        .to_path_buf();
+
+    let (events, changes) = channel();
+    let mut watcher = notify::recommended_watcher(events)?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+
+    thread::spawn(move || {
+        //the watcher stops when it's dropped
+        let _watcher = watcher;
+        while let Ok(event) = changes.recv() {
+            if !changes_file(&event, &path) {
+                continue;
+            }
+            settle(&changes);
+            println!("Config file changed, reloading");
+            if !control::notify(&requests, ControlCommand::Reload) {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+///Tells the daemon to reload whenever images are added to or removed from its directory, so the
+///schedule always lines up with them. Stops watching once the returned watcher is dropped
+pub fn watch_directory(
+    setup: &Setup,
+    requests: Sender<Request>,
+) -> Result<RecommendedWatcher, Box<dyn Error>> {
+    let (events, changes) = channel();
+    let mut watcher = notify::recommended_watcher(events)?;
+    //the phase directories are inside the directory when syncing to the sun
+    let mode = if setup.min_depth > 1 {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher.watch(Path::new(&setup.dir), mode)?;
+
+    thread::spawn(move || {
+        //ends when the watcher is dropped, since that closes the channel
+        while let Ok(event) = changes.recv() {
+            if !changes_images(&event) {
+                continue;
+            }
+            settle(&changes);
+            println!("Wallpaper directory changed, reloading");
+            if !control::notify(&requests, ControlCommand::Reload) {
+                break;
+            }
+        }
+    });
+    Ok(watcher)
+}
+
+//hidden files are never used as images, so changes to them (like thumbnail caches) don't matter
+fn changes_images(event: &notify::Result<Event>) -> bool {
+    match event {
+        Ok(event) => {
+            !matches!(event.kind, EventKind::Access(_))
+                && event.paths.iter().any(|changed| {
+                    !changed
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with('.'))
+                })
+        }
+        Err(_) => false,
+    }
+}
+
+//true if the event is something other than the file being read
+fn changes_file(event: &notify::Result<Event>, path: &Path) -> bool {
+    match event {
+        Ok(event) => {
+            !matches!(event.kind, EventKind::Access(_))
+                && event
+                    .paths
+                    .iter()
+                    .any(|changed| changed.file_name() == path.file_name())
+        }
+        Err(_) => false,
+    }
+}
+
+//waits until no more changes come in for a moment
+fn settle<T>(changes: &Receiver<T>) {
+    while changes.recv_timeout(SETTLE_TIME).is_ok() {}
+}
+
+///Tells the daemon to reload whenever it receives SIGHUP
+#[cfg(unix)]
+pub fn reload_on_hangup(requests: Sender<Request>) -> Result<(), Box<dyn Error>> {
+    use signal_hook::{consts::SIGHUP, iterator::Signals};
+
+    let mut signals = Signals::new([SIGHUP])?;
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            println!("Received SIGHUP, reloading");
+            if !control::notify(&requests, ControlCommand::Reload) {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+//there's no SIGHUP, the config file being watched is enough
+#[cfg(not(unix))]
+pub fn reload_on_hangup(_requests: Sender<Request>) -> Result<(), Box<dyn Error>> {
+    Ok(())
+}